@@ -0,0 +1,144 @@
+use std::collections::HashMap;
+
+use crate::parameters::Domain;
+use crate::particle::{Body, Float, VectN};
+
+/* Rodzaj połączenia dwóch cząsteczek. */
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum BondKind {
+    /* Sprężyna harmoniczna o długości spoczynkowej `rest_length` i sztywności `stiffness`.
+     * `damping` tłumi drgania wzdłuż sprężyny. */
+    Spring { rest_length: Float, stiffness: Float, damping: Float },
+    /* Sztywny pręt: odległość cząsteczek jest zawsze równa `length`. */
+    Rigid { length: Float },
+}
+
+/* Wiązanie między cząsteczkami o ID `a` i `b`. */
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Bond {
+    pub a: u32,
+    pub b: u32,
+    pub kind: BondKind,
+}
+
+impl Bond {
+    /* Siła sprężyny działająca na `a` (na `b` działa przeciwna). Dla sztywnych wiązań zero,
+     * bo te są obsługiwane w `apply_constraints`. */
+    pub fn force<const D: usize>(&self, a: &Body<D>, b: &Body<D>, domain: &impl Domain<D>) -> VectN<D> {
+        match self.kind {
+            BondKind::Spring { rest_length, stiffness, damping } => {
+                let d = domain.minimum_image(a.position - b.position);
+                let length = d.magnitude();
+
+                if length == 0.0 || !length.is_finite() {
+                    return VectN::zeros();
+                }
+
+                let direction = d / length;
+                let stretch_velocity = (a.velocity - b.velocity).dot(&direction);
+
+                return -(stiffness * (length - rest_length) + damping * stretch_velocity) * direction;
+            }
+
+            BondKind::Rigid { .. } => return VectN::zeros(),
+        }
+    }
+}
+
+/* Indeksy cząsteczek w `particles` po ID, żeby nie szukać ich liniowo dla każdego wiązania. */
+fn indices<const D: usize>(particles: &[Body<D>]) -> HashMap<u32, usize> {
+    return particles.iter().enumerate().map(|(i, p)| (p.id, i)).collect();
+}
+
+/* Dodaje siły sprężyn z `bonds` do sił `forces` (w tej samej kolejności co `particles`). */
+pub fn add_bond_forces<const D: usize>(bonds: &[Bond], particles: &[Body<D>], domain: &impl Domain<D>, forces: &mut [VectN<D>]) {
+    if bonds.is_empty() {
+        return;
+    }
+
+    let indices = indices(particles);
+
+    for bond in bonds {
+        if let (Some(&i), Some(&j)) = (indices.get(&bond.a), indices.get(&bond.b)) {
+            let force = bond.force(&particles[i], &particles[j], domain);
+            forces[i] += force;
+            forces[j] -= force;
+        }
+    }
+}
+
+/* Energia sprężystości wszystkich sprężyn, 1/2 k (d - d0)^2. Sztywne wiązania jej nie mają,
+ * a energia tracona przez tłumienie po prostu znika z układu. */
+pub fn potential_energy<const D: usize>(bonds: &[Bond], particles: &[Body<D>], domain: &impl Domain<D>) -> Float {
+    if bonds.is_empty() {
+        return 0.0;
+    }
+
+    let indices = indices(particles);
+
+    return bonds
+        .iter()
+        .filter_map(|bond| match (bond.kind, indices.get(&bond.a), indices.get(&bond.b)) {
+            (BondKind::Spring { rest_length, stiffness, .. }, Some(&i), Some(&j)) => {
+                let length = domain.minimum_image(particles[i].position - particles[j].position).magnitude();
+                Some(0.5 * stiffness * (length - rest_length).powi(2))
+            }
+            _ => None,
+        })
+        .sum();
+}
+
+/* Przywraca długości sztywnych wiązań po kroku integratora i usuwa składową prędkości
+ * względnej wzdłuż każdego z nich. Przesunięcia są dzielone odwrotnie proporcjonalnie do mas,
+ * więc środek masy każdej pary się nie rusza (chyba że któraś cząsteczka jest nieruchoma).
+ *
+ * Jedna cząsteczka może mieć kilka wiązań (np. łańcuch), a poprawienie jednego psuje trochę
+ * sąsiednie, dlatego powtarzamy to kilka razy. */
+pub fn apply_constraints<const D: usize>(bonds: &[Bond], particles: &mut [Body<D>], domain: &impl Domain<D>) {
+    const ITERATIONS: usize = 10;
+
+    if !bonds.iter().any(|b| matches!(b.kind, BondKind::Rigid { .. })) {
+        return;
+    }
+
+    let indices = indices(particles);
+
+    for _ in 0..ITERATIONS {
+        for bond in bonds {
+            let BondKind::Rigid { length } = bond.kind else { continue };
+
+            if let (Some(&i), Some(&j)) = (indices.get(&bond.a), indices.get(&bond.b)) {
+                let (a, b) = (&particles[i], &particles[j]);
+                let d = domain.minimum_image(b.position - a.position);
+                let distance = d.magnitude();
+
+                if distance == 0.0 || !distance.is_finite() {
+                    continue;
+                }
+
+                /* Nieruchoma cząsteczka ma zerową odwrotność masy, więc cała poprawka idzie na drugą. */
+                let (inverse_mass_a, inverse_mass_b) = (a.inverse_mass(), b.inverse_mass());
+                let total_inverse_mass = inverse_mass_a + inverse_mass_b;
+
+                if total_inverse_mass == 0.0 {
+                    continue;
+                }
+
+                let direction = d / distance;
+                let correction = direction * (distance - length);
+                let impulse = (b.velocity - a.velocity).dot(&direction) / total_inverse_mass;
+
+                particles[i].position += correction * inverse_mass_a / total_inverse_mass;
+                particles[i].velocity += direction * impulse * inverse_mass_a;
+                particles[j].position -= correction * inverse_mass_b / total_inverse_mass;
+                particles[j].velocity -= direction * impulse * inverse_mass_b;
+            }
+        }
+    }
+}
+
+/* Usuwa wiązania, których któraś cząsteczka już nie istnieje (np. połączyła się z inną). */
+pub fn remove_dangling<const D: usize>(bonds: &mut Vec<Bond>, particles: &[Body<D>]) {
+    let indices = indices(particles);
+    bonds.retain(|b| indices.contains_key(&b.a) && indices.contains_key(&b.b));
+}
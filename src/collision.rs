@@ -0,0 +1,195 @@
+use crate::parameters::{CollisionMode, Domain, MergePolicy, SimulationParameters};
+use crate::particle::{Body, Float, VectN};
+
+/* Informacja o dwóch cząsteczkach, które połączyły się w jedną. */
+#[derive(Clone, Copy, Debug)]
+pub struct Merge {
+    pub removed: [u32; 2],
+    pub created: u32,
+}
+
+/* Rozwiązuje zderzenia wszystkich par nakładających się cząsteczek.
+ *
+ * Przy `CollisionMode::Bounce` nakładające się cząsteczki są rozsuwane (lżejsza bardziej),
+ * a jeśli się do siebie zbliżają, to dostają impuls wzdłuż linii środków zależny od
+ * współczynnika restytucji. Przy `CollisionMode::Merge` są zastępowane jedną nową cząsteczką
+ * o ID `next_particle_id`. Zwraca listę połączeń, żeby reszta programu mogła np. przenieść
+ * śledzenie na nową cząsteczkę. */
+pub fn resolve_collisions<const D: usize>(
+    particles: &mut Vec<Body<D>>,
+    params: &SimulationParameters,
+    domain: &impl Domain<D>,
+    next_particle_id: &mut u32,
+) -> Vec<Merge> {
+    let mut merges = Vec::new();
+
+    if params.collision_mode == CollisionMode::None {
+        return merges;
+    }
+
+    /* Każda cząsteczka może się połączyć najwyżej raz w jednym podkroku, kolejne
+     * połączenia (np. trzech cząsteczek naraz) zrobią się w następnych podkrokach. */
+    let mut removed = vec![false; particles.len()];
+    let mut created = Vec::new();
+
+    for (i, j) in overlapping_pairs(particles, domain) {
+        if params.collision_mode == CollisionMode::Merge && !removed[i] && !removed[j] {
+            if let Some(merged) = merge(&particles[i], &particles[j], *next_particle_id, params.merge_policy, domain) {
+                merges.push(Merge { removed: [particles[i].id, particles[j].id], created: merged.id });
+                *next_particle_id += 1;
+                removed[i] = true;
+                removed[j] = true;
+                created.push(merged);
+                continue;
+            }
+        }
+
+        if !removed[i] && !removed[j] {
+            let (left, right) = particles.split_at_mut(j);
+            collide(&mut left[i], &mut right[0], params.restitution, domain);
+        }
+    }
+
+    if !created.is_empty() {
+        let mut index = 0;
+        particles.retain(|_| {
+            index += 1;
+            !removed[index - 1]
+        });
+        particles.append(&mut created);
+    }
+
+    return merges;
+}
+
+/* Nowa cząsteczka powstała z połączenia `a` i `b`, albo `None`, jeśli `policy` nie pozwala ich połączyć.
+ * Nieruchome cząsteczki nigdy się nie łączą, tylko odbijają. */
+fn merge<const D: usize>(a: &Body<D>, b: &Body<D>, id: u32, policy: MergePolicy, domain: &impl Domain<D>) -> Option<Body<D>> {
+    if a.fixed || b.fixed {
+        return None;
+    }
+
+    let mut mass = a.mass + b.mass;
+    let mut charge = a.charge + b.charge;
+
+    if mass > 1.0 || !(-1.0..=1.0).contains(&charge) {
+        match policy {
+            MergePolicy::Allow => {}
+            MergePolicy::Clamp => {
+                mass = mass.min(1.0);
+                charge = charge.clamp(-1.0, 1.0);
+            }
+            MergePolicy::Bounce => return None,
+        }
+    }
+
+    /* Położenie i prędkość środka masy, więc pęd się zgadza (chyba że masa została przycięta,
+     * wtedy zachowana jest tylko prędkość środka masy). */
+    let total_mass = a.mass + b.mass;
+    /* Przy okresowych brzegach `b` może być po drugiej stronie pudełka, więc bierzemy jej
+     * najbliższy obraz. Wynik może wyjść poza pudełko, ale zaraz i tak wraca przez `apply_limits`. */
+    let b_position = a.position + domain.minimum_image(b.position - a.position);
+
+    return Some(Body {
+        id,
+        position: (a.position * a.mass + b_position * b.mass) / total_mass,
+        velocity: (a.velocity * a.mass + b.velocity * b.mass) / total_mass,
+        acceleration: (a.acceleration * a.mass + b.acceleration * b.mass) / total_mass,
+        charge,
+        mass,
+        /* Zachowujemy pole powierzchni w 2D, a objętość w 3D. */
+        radius: (a.radius.powi(D as i32) + b.radius.powi(D as i32)).powf(1.0 / D as Float),
+        fixed: false,
+    });
+}
+
+/* Pary indeksów (i < j) cząsteczek, które się nakładają.
+ *
+ * Sprawdzanie wszystkich par w każdym podkroku jest za wolne, więc sortujemy cząsteczki
+ * po lewej krawędzi i dla każdej sprawdzamy tylko te, które zaczynają się przed jej prawą krawędzią. */
+fn overlapping_pairs<const D: usize>(particles: &[Body<D>], domain: &impl Domain<D>) -> Vec<(usize, usize)> {
+    let mut order: Vec<usize> = (0..particles.len())
+        .filter(|&i| particles[i].position.magnitude_squared().is_finite())
+        .collect();
+    order.sort_by(|&a, &b| {
+        let left_a = particles[a].position[0] - particles[a].radius;
+        let left_b = particles[b].position[0] - particles[b].radius;
+        left_a.total_cmp(&left_b)
+    });
+
+    let overlap = |a: usize, b: usize| {
+        let min_distance = particles[a].radius + particles[b].radius;
+        domain.minimum_image(particles[a].position - particles[b].position).magnitude_squared() < min_distance.powi(2)
+    };
+
+    let mut pairs = Vec::new();
+    for (k, &a) in order.iter().enumerate() {
+        let right_a = particles[a].position[0] + particles[a].radius;
+
+        for &b in order[k + 1..].iter() {
+            if particles[b].position[0] - particles[b].radius > right_a {
+                break;
+            }
+
+            if overlap(a, b) {
+                pairs.push((a.min(b), a.max(b)));
+            }
+        }
+    }
+
+    /* Przy okresowym brzegu w x stykać się mogą też cząsteczki przy lewej i prawej ścianie,
+     * a tych samo sortowanie nie znajdzie. Takich cząsteczek jest mało, więc sprawdzamy
+     * je parami. Okresowość pozostałych osi nie przeszkadza, bo sortujemy tylko po x. */
+    if domain.periodic_axes()[0] {
+        let (min, max) = domain.bounds();
+        let max_radius = particles.iter().map(|p| p.radius).fold(0.0, Float::max);
+        let near = |edge: Float| -> Vec<usize> {
+            order.iter().copied().filter(|&i| (particles[i].position[0] - edge).abs() < 2.0 * max_radius).collect()
+        };
+        let (left, right) = (near(min[0]), near(max[0]));
+
+        for &a in &left {
+            for &b in &right {
+                if a != b && overlap(a, b) {
+                    pairs.push((a.min(b), a.max(b)));
+                }
+            }
+        }
+
+        pairs.sort_unstable();
+        pairs.dedup();
+    }
+
+    return pairs;
+}
+
+/* Zderzenie dwóch nakładających się cząsteczek. */
+fn collide<const D: usize>(a: &mut Body<D>, b: &mut Body<D>, restitution: Float, domain: &impl Domain<D>) {
+    let r = domain.minimum_image(b.position - a.position);
+    let distance = r.magnitude();
+
+    /* Gdy środki się pokrywają, kierunek jest dowolny. */
+    let normal = if distance > 0.0 { r / distance } else { VectN::from_fn(|axis, _| if axis == 0 { 1.0 } else { 0.0 }) };
+    let overlap = a.radius + b.radius - distance;
+    let (inverse_mass_a, inverse_mass_b) = (a.inverse_mass(), b.inverse_mass());
+    let total_inverse_mass = inverse_mass_a + inverse_mass_b;
+
+    /* Dwie nieruchome cząsteczki po prostu na siebie nachodzą. */
+    if total_inverse_mass == 0.0 {
+        return;
+    }
+
+    /* Lżejsza cząsteczka przesuwa się bardziej, a nieruchoma wcale. */
+    a.position -= normal * overlap * inverse_mass_a / total_inverse_mass;
+    b.position += normal * overlap * inverse_mass_b / total_inverse_mass;
+
+    /* Składowa prędkości względnej wzdłuż linii środków. Jeśli jest dodatnia, to
+     * cząsteczki już się od siebie oddalają i nie trzeba nic robić. */
+    let approach_velocity = (b.velocity - a.velocity).dot(&normal);
+    if approach_velocity < 0.0 {
+        let impulse = -(1.0 + restitution) * approach_velocity / total_inverse_mass;
+
+        a.velocity -= normal * impulse * inverse_mass_a;
+        b.velocity += normal * impulse * inverse_mass_b;
+    }
+}
@@ -0,0 +1,143 @@
+use crate::force::ExternalField;
+use crate::parameters::{DragModel, SimulationParameters};
+use crate::particle::{from_plane, Body, Float, VectN};
+
+/* Opór jako pole zewnętrzne w `Interactions`. Jako zwykła siła nic nie robi,
+ * bo cały opór jest w dokładnym kroku `apply_drag`. */
+pub struct Drag;
+
+impl<const D: usize> ExternalField<D> for Drag {
+    fn name(&self) -> &'static str {
+        return "Opór";
+    }
+
+    fn active(&self, params: &SimulationParameters) -> bool {
+        return coefficients(params).is_some();
+    }
+
+    fn force(&self, _p: &Body<D>, _params: &SimulationParameters, _time: Float) -> VectN<D> {
+        return VectN::zeros();
+    }
+
+    fn potential_energy(&self, _p: &Body<D>, _params: &SimulationParameters, _time: Float) -> Option<Float> {
+        return None;
+    }
+
+    fn exact_step(&self, particles: &mut [Body<D>], params: &SimulationParameters, d_time: Float) -> Float {
+        return apply_drag(particles, params, d_time);
+    }
+}
+
+/* Opór ruchu względem płynu poruszającego się z prędkością `params.fluid_velocity`
+ * (w 3D płyn płynie w płaszczyźnie xy).
+ *
+ * Opór nie jest liczony jako zwykła siła w integratorze, tylko osobnym krokiem po nim
+ * (`ExternalField::exact_step`), z dokładnego rozwiązania równania m du/dt = -(b u + c u^2),
+ * gdzie u to szybkość względem płynu, b to `params.linear_drag_k`, a c to `params.drag_k`.
+ * Dzięki temu:
+ * - przy silnym oporze prędkość nie przeskakuje przez zero (jawne całkowanie by to robiło
+ *   już przy b dt / m > 1),
+ * - wolne cząsteczki zwalniają płynnie aż do zatrzymania, a nie "zamierają" przy prędkości,
+ *   przy której c u^2 jest za małe, żeby cokolwiek zmienić w precyzji floatów.
+ *
+ * Kierunek prędkości względnej się nie zmienia, zmniejsza się tylko jej wartość.
+ * Zwraca energię kinetyczną, którą cząsteczki straciły (przy płynącym płynie może być ujemna,
+ * bo płyn cząsteczki rozpędza). */
+pub fn apply_drag<const D: usize>(particles: &mut [Body<D>], params: &SimulationParameters, d_time: Float) -> Float {
+    let Some((b, c)) = coefficients(params) else { return 0.0 };
+    let mut dissipated = 0.0;
+
+    let fluid_velocity = from_plane::<D>(params.fluid_velocity);
+
+    for p in particles.iter_mut().filter(|p| !p.fixed) {
+        let relative = p.velocity - fluid_velocity;
+        let speed = relative.magnitude();
+
+        if speed == 0.0 || !speed.is_finite() {
+            continue;
+        }
+
+        let old_energy = 0.5 * p.mass * p.velocity.magnitude_squared();
+        p.velocity = fluid_velocity + relative * (slowed_speed(speed, p.mass, b, c, d_time) / speed);
+        dissipated += old_energy - 0.5 * p.mass * p.velocity.magnitude_squared();
+    }
+
+    return dissipated;
+}
+
+/* Współczynniki (b, c) oporu przy tych parametrach albo `None`, jeśli opór nic nie robi. */
+fn coefficients(params: &SimulationParameters) -> Option<(Float, Float)> {
+    let (b, c) = match params.drag_model {
+        DragModel::None => return None,
+        DragModel::Linear => (params.linear_drag_k, 0.0),
+        DragModel::Quadratic => (0.0, params.drag_k),
+        DragModel::Combined => (params.linear_drag_k, params.drag_k),
+    };
+
+    return if b == 0.0 && c == 0.0 { None } else { Some((b, c)) };
+}
+
+/* Szybkość względem płynu po czasie `d_time`, jeśli na początku była równa `speed`. */
+fn slowed_speed(speed: Float, mass: Float, b: Float, c: Float, d_time: Float) -> Float {
+    return if b > 0.0 {
+        /* Rozwiązanie równania Bernoulliego; dla c = 0 to zwykły zanik wykładniczy. */
+        let decay = (-b * d_time / mass).exp();
+        b * speed * decay / (b + c * speed * (1.0 - decay))
+    } else {
+        speed / (1.0 + c * speed * d_time / mass)
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::force::Interactions;
+    use crate::particle::{Particle, Vect};
+
+    /* Szybkość po czasie `d_time` z równania m du/dt = -(b u + c u^2) całkowanego RK4
+     * w bardzo małych krokach. */
+    fn integrated_speed(speed: Float, mass: Float, b: Float, c: Float, d_time: Float) -> Float {
+        const STEPS: usize = 10_000;
+        let derivative = |u: Float| -(b * u + c * u * u) / mass;
+        let h = d_time / STEPS as Float;
+        let mut u = speed;
+
+        for _ in 0..STEPS {
+            let k1 = derivative(u);
+            let k2 = derivative(u + k1 * h / 2.0);
+            let k3 = derivative(u + k2 * h / 2.0);
+            let k4 = derivative(u + k3 * h);
+            u += (k1 + 2.0 * k2 + 2.0 * k3 + k4) * h / 6.0;
+        }
+
+        return u;
+    }
+
+    #[test]
+    fn slowed_speed_solves_drag_equation() {
+        for (b, c) in [(0.5, 0.0), (0.0, 0.8), (0.5, 0.8), (20.0, 3.0)] {
+            for (speed, d_time) in [(2.0, 0.1), (0.01, 1.0), (5.0, 0.5)] {
+                let exact = slowed_speed(speed, 0.5, b, c, d_time);
+                let integrated = integrated_speed(speed, 0.5, b, c, d_time);
+
+                assert!((exact - integrated).abs() <= 1e-4 * speed, "b = {}, c = {}: {} != {}", b, c, exact, integrated);
+            }
+        }
+    }
+
+    #[test]
+    fn drag_keeps_direction_relative_to_fluid() {
+        let mut params = SimulationParameters { drag_model: DragModel::Combined, ..SimulationParameters::default() };
+        params.fluid_velocity = Vect::new(1.0, 0.0);
+
+        let mut particles = vec![Particle::new(0, 0.5, 0.5, 0.0, 0.5, 0.01)];
+        particles[0].velocity = Vect::new(1.0, 3.0);
+
+        let dissipated = Interactions::builtin().exact_steps(&mut particles, &params, 0.1);
+        let relative = particles[0].velocity - params.fluid_velocity;
+
+        assert_eq!(relative.x, 0.0);
+        assert!(0.0 < relative.y && relative.y < 3.0);
+        assert!((dissipated - 0.25 * (10.0 - particles[0].velocity.magnitude_squared())).abs() < 1e-5);
+    }
+}
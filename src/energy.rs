@@ -0,0 +1,121 @@
+use std::collections::VecDeque;
+
+use crate::particle::{Body, Float};
+
+/* Energia kinetyczna wszystkich cząsteczek (nieruchome i tak mają zerową prędkość). */
+pub fn kinetic_energy<const D: usize>(particles: &[Body<D>]) -> Float {
+    return particles
+        .iter()
+        .map(|p| 0.5 * p.mass * p.velocity.magnitude_squared())
+        .filter(|e| e.is_finite())
+        .sum();
+}
+
+/* Przebieg w czasie składowych energii układu, ich sumy i względnego dryfu sumy.
+ *
+ * Składowe to energia kinetyczna, energie potencjalne poszczególnych oddziaływań i energia
+ * rozproszona przez opór, więc suma powinna być stała. Jeśli nie jest, a w układzie nie ma
+ * innych strat (zderzeń niesprężystych, termostatu, tłumienia wiązań, pól zależnych od czasu),
+ * to energię psuje metoda całkowania albo za długi krok. */
+pub struct EnergyHistory {
+    /* Nazwa składowej i punkty (czas, wartość). */
+    pub components: Vec<(String, VecDeque<[f64; 2]>)>,
+    pub total: VecDeque<[f64; 2]>,
+    /* Punkty (czas, (E - E0) / skala), gdzie E0 to energia całkowita na początku pomiaru. */
+    pub drift: VecDeque<[f64; 2]>,
+    /* Energia całkowita i skala z początku pomiaru oraz to, od czego zależały. */
+    baseline: Option<Baseline>,
+}
+
+struct Baseline {
+    total: Float,
+    scale: Float,
+    particle_count: usize,
+    names: Vec<String>,
+}
+
+impl EnergyHistory {
+    /* Dla ilu chwil czasu chcemy trzymać wartości. */
+    pub const DATA_POINT_COUNT: usize = 1024;
+
+    pub fn new() -> Self {
+        return Self {
+            components: Vec::new(),
+            total: VecDeque::with_capacity(Self::DATA_POINT_COUNT),
+            drift: VecDeque::with_capacity(Self::DATA_POINT_COUNT),
+            baseline: None,
+        };
+    }
+
+    pub fn clear(&mut self) {
+        *self = Self::new();
+    }
+
+    /* Zapisuje składowe energii `components` z chwili `time`.
+     *
+     * Dryf liczymy względem sumy wartości bezwzględnych składowych, a nie samej energii
+     * całkowitej, bo ta może być bliska zeru (np. para przyciągających się ładunków).
+     * Po dodaniu albo usunięciu cząsteczek, albo włączeniu nowego oddziaływania energia
+     * całkowita skacze, więc wtedy zaczynamy mierzyć dryf od nowa. */
+    pub fn record(&mut self, time: Float, components: &[(&str, Float)], particle_count: usize) {
+        let total: Float = components.iter().map(|(_, e)| e).sum();
+        let names: Vec<String> = components.iter().map(|(name, _)| name.to_string()).collect();
+
+        if !total.is_finite() {
+            return;
+        }
+
+        let outdated = match self.baseline {
+            Some(ref baseline) => baseline.particle_count != particle_count || baseline.names != names,
+            None => true,
+        };
+
+        if outdated {
+            self.drift.clear();
+            self.baseline = Some(Baseline {
+                total,
+                scale: components.iter().map(|(_, e)| e.abs()).sum(),
+                particle_count,
+                names,
+            });
+        }
+
+        for &(name, energy) in components {
+            let index = match self.components.iter().position(|(n, _)| n == name) {
+                Some(index) => index,
+                None => {
+                    self.components.push((name.to_string(), VecDeque::with_capacity(Self::DATA_POINT_COUNT)));
+                    self.components.len() - 1
+                }
+            };
+
+            push(&mut self.components[index].1, [time as f64, energy as f64], Self::DATA_POINT_COUNT);
+        }
+
+        /* Składowe, które przestały być liczone, znikają, gdy ich ostatni punkt wyjdzie poza wykres. */
+        let oldest = self.total.front().map_or(time as f64, |point| point[0]);
+        self.components.retain(|(_, points)| points.back().is_some_and(|point| point[0] >= oldest));
+
+        push(&mut self.total, [time as f64, total as f64], Self::DATA_POINT_COUNT);
+
+        let baseline = self.baseline.as_ref().unwrap();
+        if baseline.scale > 0.0 {
+            push(&mut self.drift, [time as f64, ((total - baseline.total) / baseline.scale) as f64], Self::DATA_POINT_COUNT);
+        }
+    }
+}
+
+impl Default for EnergyHistory {
+    fn default() -> Self {
+        return Self::new();
+    }
+}
+
+/* Dopisuje punkt, usuwając najstarszy, jeśli jest ich już `capacity`. */
+pub fn push(points: &mut VecDeque<[f64; 2]>, point: [f64; 2], capacity: usize) {
+    if points.len() >= capacity {
+        points.pop_front();
+    }
+
+    points.push_back(point);
+}
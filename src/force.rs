@@ -0,0 +1,361 @@
+use crate::drag::Drag;
+use crate::parameters::{Domain, PairPotential, SimulationParameters};
+use crate::particle::{from_plane, plane, Body, Float, VectN};
+use crate::quadtree::{ForceBackend, QuadTree};
+
+/* Stała oddziaływania i funkcja zwracająca źródło pola cząsteczki (np. ładunek albo masę). */
+pub type LongRange<const D: usize> = (Float, fn(&Body<D>) -> Float);
+
+/* Oddziaływanie między parą cząsteczek w przestrzeni o `D` wymiarach.
+ *
+ * Żeby dodać do symulacji nową siłę, wystarczy zaimplementować ten trait (albo `ExternalField`)
+ * i dopisać obiekt do `Interactions`, bez ruszania `particle.rs`. Wbudowane oddziaływania
+ * są zaimplementowane dla każdego `D`, więc działają i w 2D, i w 3D. */
+pub trait ForceLaw<const D: usize> {
+    fn name(&self) -> &'static str;
+
+    /* Czy przy tych parametrach oddziaływanie w ogóle coś robi. Nieaktywne są pomijane. */
+    fn active(&self, _params: &SimulationParameters) -> bool {
+        return true;
+    }
+
+    /* Siła działająca na `p` od `other`, gdy `r` to wektor od `other` do `p` (przy okresowych
+     * ścianach już do najbliższego obrazu `other`, patrz `Domain::minimum_image`). */
+    fn force(&self, p: &Body<D>, other: &Body<D>, r: VectN<D>, params: &SimulationParameters) -> VectN<D>;
+
+    /* Energia potencjalna pary (z dokładnością do stałej), zgodna z `force`. */
+    fn potential_energy(&self, p: &Body<D>, other: &Body<D>, r: VectN<D>, params: &SimulationParameters) -> Float;
+
+    /* Jeśli siła ma postać `coupling * s(p) * s(other) * params.force_kernel(r^2) * r`,
+     * gdzie s to np. ładunek albo masa, to zwraca (coupling, s), i wtedy przy
+     * `ForceBackend::BarnesHut` jest liczona drzewem. */
+    fn long_range(&self, _params: &SimulationParameters) -> Option<LongRange<D>> {
+        return None;
+    }
+
+    /* Suma sił działających na `p` od wszystkich cząsteczek z `particles` (oprócz niej samej). */
+    fn net_force(&self, p: &Body<D>, particles: &[Body<D>], params: &SimulationParameters, domain: &dyn Domain<D>) -> VectN<D> {
+        return particles
+            .iter()
+            .filter(|other| other.id != p.id)
+            .fold(VectN::zeros(), |acc, other| acc + self.force(p, other, domain.minimum_image(p.position - other.position), params));
+    }
+}
+
+/* Siła działająca na każdą cząsteczkę osobno, niezależnie od pozostałych.
+ * Może zależeć od czasu symulacji `time`. */
+pub trait ExternalField<const D: usize> {
+    fn name(&self) -> &'static str;
+
+    fn active(&self, _params: &SimulationParameters) -> bool {
+        return true;
+    }
+
+    fn force(&self, p: &Body<D>, params: &SimulationParameters, time: Float) -> VectN<D>;
+
+    /* Energia potencjalna cząsteczki w tym polu (z dokładnością do stałej), zgodna z `force`,
+     * albo `None`, jeśli pole energię tylko rozprasza (np. opór).
+     * Jeśli pole zależy od czasu, to energia zmienia się też bez ruchu cząsteczek, więc
+     * całkowita energia układu nie musi być wtedy zachowana. */
+    fn potential_energy(&self, p: &Body<D>, params: &SimulationParameters, time: Float) -> Option<Float>;
+
+    /* Dokładny krok pola o czas `d_time`, wykonywany po kroku integratora.
+     *
+     * Niektórych pól nie da się dobrze całkować jako zwykłej siły (np. silny opór, przy którym
+     * jawny krok przeskakuje przez zero). Takie pole zwraca zero z `force`, a prędkości zmienia
+     * tutaj, z dokładnego rozwiązania. Zwraca energię kinetyczną, którą cząsteczki przy tym straciły. */
+    fn exact_step(&self, _particles: &mut [Body<D>], _params: &SimulationParameters, _d_time: Float) -> Float {
+        return 0.0;
+    }
+}
+
+/* Oddziaływanie elektrostatyczne. W 2D siła maleje jak 1/r, a w 3D jak 1/r^2. */
+pub struct Coulomb;
+
+impl<const D: usize> ForceLaw<D> for Coulomb {
+    fn name(&self) -> &'static str {
+        return "Coulomb";
+    }
+
+    fn active(&self, params: &SimulationParameters) -> bool {
+        return params.electro_k != 0.0;
+    }
+
+    fn force(&self, p: &Body<D>, other: &Body<D>, r: VectN<D>, params: &SimulationParameters) -> VectN<D> {
+        /* Kwadrat długości `r` może być zero, gdy dwie cząsteczki się na siebie nałożą,
+         * tym zajmuje się zmiękczenie z `params.softening`. */
+        return params.electro_k * p.charge * other.charge * params.force_kernel::<D>(r.magnitude_squared()) * r;
+    }
+
+    fn potential_energy(&self, p: &Body<D>, other: &Body<D>, r: VectN<D>, params: &SimulationParameters) -> Float {
+        return params.electro_k * p.charge * other.charge * params.potential_kernel::<D>(r.magnitude_squared());
+    }
+
+    fn long_range(&self, params: &SimulationParameters) -> Option<LongRange<D>> {
+        return Some((params.electro_k, |p| p.charge));
+    }
+}
+
+/* Wzajemne przyciąganie grawitacyjne. Maleje z odległością tak samo jak siła elektrostatyczna
+ * i ma to samo zmiękczenie, tylko zamiast ładunków są masy, a zamiast odpychania przyciąganie. */
+pub struct MutualGravity;
+
+impl<const D: usize> ForceLaw<D> for MutualGravity {
+    fn name(&self) -> &'static str {
+        return "Grawitacja wzajemna";
+    }
+
+    fn active(&self, params: &SimulationParameters) -> bool {
+        return params.gravity_mode.mutual() && params.mutual_gravity_k != 0.0;
+    }
+
+    fn force(&self, p: &Body<D>, other: &Body<D>, r: VectN<D>, params: &SimulationParameters) -> VectN<D> {
+        return -params.mutual_gravity_k * p.mass * other.mass * params.force_kernel::<D>(r.magnitude_squared()) * r;
+    }
+
+    fn potential_energy(&self, p: &Body<D>, other: &Body<D>, r: VectN<D>, params: &SimulationParameters) -> Float {
+        return -params.mutual_gravity_k * p.mass * other.mass * params.potential_kernel::<D>(r.magnitude_squared());
+    }
+
+    fn long_range(&self, params: &SimulationParameters) -> Option<LongRange<D>> {
+        return Some((-params.mutual_gravity_k, |p| p.mass));
+    }
+}
+
+/* Potencjał krótkozasięgowy `params.pair_potential` (Lennard-Jones albo WCA).
+ *
+ * Jest krótkozasięgowy, więc drzewo nic by tu nie dało; dalekie pary i tak odpadają
+ * na samym sprawdzeniu odległości. */
+pub struct ShortRangeRepulsion;
+
+impl<const D: usize> ForceLaw<D> for ShortRangeRepulsion {
+    fn name(&self) -> &'static str {
+        return "Odpychanie krótkozasięgowe";
+    }
+
+    fn active(&self, params: &SimulationParameters) -> bool {
+        return params.pair_potential != PairPotential::None;
+    }
+
+    fn force(&self, _p: &Body<D>, _other: &Body<D>, r: VectN<D>, params: &SimulationParameters) -> VectN<D> {
+        let r_len_sq = r.magnitude_squared();
+        let cutoff = params.pair_potential.cutoff() * params.lj_sigma;
+
+        if !r_len_sq.is_finite() || r_len_sq == 0.0 || r_len_sq >= cutoff * cutoff {
+            return VectN::zeros();
+        }
+
+        /* F = 24ε/r^2 * (2(σ/r)^12 - (σ/r)^6) * r */
+        let sr6 = (params.lj_sigma * params.lj_sigma / r_len_sq).powi(3);
+        return 24.0 * params.lj_epsilon * (2.0 * sr6 * sr6 - sr6) / r_len_sq * r;
+    }
+
+    fn potential_energy(&self, _p: &Body<D>, _other: &Body<D>, r: VectN<D>, params: &SimulationParameters) -> Float {
+        let r_len_sq = r.magnitude_squared();
+        let cutoff = params.pair_potential.cutoff();
+
+        if !r_len_sq.is_finite() || r_len_sq == 0.0 || r_len_sq >= (cutoff * params.lj_sigma).powi(2) {
+            return 0.0;
+        }
+
+        /* Potencjał przesuwamy tak, żeby w miejscu obcięcia był równy zero, bo inaczej
+         * energia skakałaby przy każdym wejściu cząsteczki w zasięg. */
+        let potential = |sr6: Float| 4.0 * params.lj_epsilon * (sr6 * sr6 - sr6);
+        let sr6 = (params.lj_sigma * params.lj_sigma / r_len_sq).powi(3);
+        return potential(sr6) - potential(cutoff.powi(-6));
+    }
+}
+
+/* Jednorodna grawitacja skierowana w dół, czyli wzdłuż -y (także w 3D). */
+pub struct UniformGravity;
+
+impl<const D: usize> ExternalField<D> for UniformGravity {
+    fn name(&self) -> &'static str {
+        return "Grawitacja jednorodna";
+    }
+
+    fn active(&self, params: &SimulationParameters) -> bool {
+        return params.gravity_mode.uniform() && params.gravity_k != 0.0;
+    }
+
+    fn force(&self, p: &Body<D>, params: &SimulationParameters, _time: Float) -> VectN<D> {
+        let mut force = VectN::zeros();
+        force[1] = -params.gravity_k * p.mass;
+        return force;
+    }
+
+    fn potential_energy(&self, p: &Body<D>, params: &SimulationParameters, _time: Float) -> Option<Float> {
+        return Some(params.gravity_k * p.mass * p.position[1]);
+    }
+}
+
+/* Zewnętrzne pole elektryczne `params.electric_field`, jednorodne w przestrzeni
+ * i leżące w płaszczyźnie xy. */
+pub struct ExternalElectric;
+
+impl<const D: usize> ExternalField<D> for ExternalElectric {
+    fn name(&self) -> &'static str {
+        return "Zewnętrzne pole elektryczne";
+    }
+
+    fn active(&self, params: &SimulationParameters) -> bool {
+        return !params.electric_field.is_zero();
+    }
+
+    fn force(&self, p: &Body<D>, params: &SimulationParameters, time: Float) -> VectN<D> {
+        return p.charge * from_plane(params.electric_field.at(time));
+    }
+
+    fn potential_energy(&self, p: &Body<D>, params: &SimulationParameters, time: Float) -> Option<Float> {
+        return Some(-p.charge * params.electric_field.at(time).dot(&plane(p.position)));
+    }
+}
+
+/* Wszystkie siły, które symulacja liczy w każdym kroku.
+ *
+ * Siła Lorentza tu nie należy, bo zależy od prędkości w sposób, który integrator
+ * (a zwłaszcza metoda Borisa) musi obsłużyć osobno. */
+pub struct Interactions<const D: usize> {
+    pub pair: Vec<Box<dyn ForceLaw<D>>>,
+    pub external: Vec<Box<dyn ExternalField<D>>>,
+}
+
+impl<const D: usize> Interactions<D> {
+    /* Wbudowane oddziaływania. Każde sprawdza w `active`, czy jest włączone w parametrach. */
+    pub fn builtin() -> Self {
+        return Self {
+            pair: vec![Box::new(Coulomb), Box::new(MutualGravity), Box::new(ShortRangeRepulsion)],
+            external: vec![Box::new(UniformGravity), Box::new(Drag), Box::new(ExternalElectric)],
+        };
+    }
+
+    /* Nazwy oddziaływań aktywnych przy tych parametrach. */
+    pub fn active_names(&self, params: &SimulationParameters) -> Vec<&'static str> {
+        return self.pair
+            .iter()
+            .filter(|law| law.active(params))
+            .map(|law| law.name())
+            .chain(self.external.iter().filter(|field| field.active(params)).map(|field| field.name()))
+            .collect();
+    }
+
+    /* Dodaje do `forces` siły pól zewnętrznych działające w chwili `time` na każdą cząsteczkę
+     * z `particles` (w tej samej kolejności). Razem z `pair_forces` daje to siły wypadkowe. */
+    pub fn add_external_forces(&self, particles: &[Body<D>], params: &SimulationParameters, time: Float, forces: &mut [VectN<D>]) {
+        for field in self.external.iter().filter(|field| field.active(params)) {
+            for (force, p) in forces.iter_mut().zip(particles) {
+                *force += field.force(p, params, time);
+            }
+        }
+    }
+
+    /* Same siły od innych cząsteczek (bez pól zewnętrznych) dla każdej cząsteczki z `particles`.
+     * Przy `ForceBackend::BarnesHut` oddziaływania dalekozasięgowe (te z `ForceLaw::long_range`)
+     * są przybliżane z kątem otwarcia `theta`.
+     * Z trzeciej zasady dynamiki ich suma powinna być zerowa; drzewo Barnesa-Huta tę zasadę
+     * łamie, bo cząsteczki widzą się nawzajem z różnym przybliżeniem. */
+    pub fn pair_forces(
+        &self,
+        particles: &[Body<D>],
+        params: &SimulationParameters,
+        domain: &impl Domain<D>,
+        backend: ForceBackend,
+        theta: Float,
+    ) -> Vec<VectN<D>> {
+        let pair: Vec<&dyn ForceLaw<D>> = self.pair.iter().filter(|law| law.active(params)).map(|law| law.as_ref()).collect();
+
+        /* Drzewo budujemy raz na krok dla każdego oddziaływania, które da się nim liczyć.
+         * Drzewo nie wie nic o obrazach okresowych, więc przy okresowych brzegach liczymy wprost. */
+        let trees: Vec<Option<(LongRange<D>, QuadTree<D>)>> = pair
+            .iter()
+            .map(|law| match backend {
+                _ if domain.periodic() => None,
+                ForceBackend::Direct => None,
+                ForceBackend::BarnesHut => law
+                    .long_range(params)
+                    .map(|(coupling, source)| ((coupling, source), QuadTree::new(particles, source))),
+            })
+            .collect();
+
+        return particles
+            .iter()
+            .map(|p| {
+                pair.iter().zip(&trees).fold(VectN::zeros(), |acc, (law, tree)| {
+                    acc + match tree {
+                        Some(((coupling, source), tree)) => *coupling * source(p) * tree.field(p.position, p.id, theta, params),
+                        None => law.net_force(p, particles, params, domain),
+                    }
+                })
+            })
+            .collect();
+    }
+
+    /* Wykonuje `ExternalField::exact_step` wszystkich aktywnych pól po kroku integratora
+     * o długości `d_time`. Zwraca energię, którą przy tym straciły cząsteczki. */
+    pub fn exact_steps(&self, particles: &mut [Body<D>], params: &SimulationParameters, d_time: Float) -> Float {
+        return self.external
+            .iter()
+            .filter(|field| field.active(params))
+            .map(|field| field.exact_step(particles, params, d_time))
+            .sum();
+    }
+
+    /* Całkowita energia potencjalna oddziaływań par w układzie (każda para raz). */
+    pub fn potential_energy(&self, particles: &[Body<D>], params: &SimulationParameters, domain: &impl Domain<D>) -> Float {
+        return self.pair
+            .iter()
+            .filter(|law| law.active(params))
+            .map(|law| pair_potential_energy(law.as_ref(), particles, params, domain))
+            .sum();
+    }
+
+    /* Energia potencjalna osobno dla każdego aktywnego oddziaływania (razem z polami
+     * zewnętrznymi, które ją mają) w chwili `time`, z nazwami jak w `active_names`. */
+    pub fn potential_energies(
+        &self,
+        particles: &[Body<D>],
+        params: &SimulationParameters,
+        domain: &impl Domain<D>,
+        time: Float,
+    ) -> Vec<(&'static str, Float)> {
+        let pair = self.pair
+            .iter()
+            .filter(|law| law.active(params))
+            .map(|law| (law.name(), pair_potential_energy(law.as_ref(), particles, params, domain)));
+
+        let external = self.external
+            .iter()
+            .filter(|field| field.active(params))
+            .filter_map(|field| {
+                let energy: Option<Float> = particles.iter().map(|p| field.potential_energy(p, params, time)).sum();
+                energy.map(|energy| (field.name(), energy))
+            });
+
+        return pair.chain(external).collect();
+    }
+}
+
+/* Energia potencjalna oddziaływania `law` dla wszystkich par z `particles` (każda para raz). */
+fn pair_potential_energy<const D: usize>(
+    law: &dyn ForceLaw<D>,
+    particles: &[Body<D>],
+    params: &SimulationParameters,
+    domain: &impl Domain<D>,
+) -> Float {
+    return particles
+        .iter()
+        .enumerate()
+        .map(|(i, p)| {
+            particles[i + 1..]
+                .iter()
+                .map(|other| law.potential_energy(p, other, domain.minimum_image(p.position - other.position), params))
+                .sum::<Float>()
+        })
+        .sum();
+}
+
+impl<const D: usize> Default for Interactions<D> {
+    fn default() -> Self {
+        return Self::builtin();
+    }
+}
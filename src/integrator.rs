@@ -0,0 +1,326 @@
+use crate::parameters::MagneticField;
+use crate::particle::{cross_z, plane, Body, Float, VectN};
+
+/* Metoda całkowania równań ruchu.
+ *
+ * Wszystkie metody działają na całym układzie naraz, a nie na pojedynczej cząsteczce,
+ * bo np. RK4 potrzebuje sił dla pośrednich stanów wszystkich cząsteczek jednocześnie. */
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Integrator {
+    /* Stara metoda z `Particle::apply_force`: przyspieszenie stałe przez cały krok.
+     * Zostawiona do porównań, bo energia w niej ucieka najszybciej. */
+    Euler,
+    /* Verlet prędkościowy (kick-drift-kick), symplektyczny, drugiego rzędu. */
+    VelocityVerlet,
+    /* Leapfrog w wersji drift-kick-drift, też symplektyczny, ale liczy siły raz na krok. */
+    Leapfrog,
+    /* Klasyczny Runge-Kutta czwartego rzędu. Nie jest symplektyczny, ale jest bardzo dokładny. */
+    RungeKutta4,
+    /* Metoda Borisa: siła Lorentza jest liczona dokładnie jako obrót prędkości, więc w polu
+     * magnetycznym szybkość się nie zmienia, a orbity cyklotronowe się nie rozkręcają. */
+    Boris,
+}
+
+impl Integrator {
+    pub const ALL: [Integrator; 5] = [
+        Integrator::Euler,
+        Integrator::VelocityVerlet,
+        Integrator::Leapfrog,
+        Integrator::RungeKutta4,
+        Integrator::Boris,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        return match self {
+            Integrator::Euler => "Euler",
+            Integrator::VelocityVerlet => "Verlet prędkościowy",
+            Integrator::Leapfrog => "Leapfrog",
+            Integrator::RungeKutta4 => "Runge-Kutta 4",
+            Integrator::Boris => "Boris",
+        };
+    }
+
+    /* Przesuwa wszystkie cząsteczki o czas `d_time`, od chwili `time`.
+     *
+     * `forces` liczy wypadkowe siły dla podanego stanu cząsteczek (w tej samej kolejności)
+     * w podanej chwili, np. `Interactions::pair_forces` razem z `Interactions::add_external_forces`. Każdy etap metody dostaje swoją chwilę
+     * (np. t + dt/2 w środku kroku RK4), bo inaczej pola zmienne w czasie psują rząd metody.
+     * Siłę Lorentza od `magnetic_field` integrator dolicza sam (pole jest skierowane wzdłuż z
+     * i zależy tylko od x i y, także w 3D). Ściany pudełka nie są tu obsługiwane. */
+    pub fn step<const D: usize>(
+        &self,
+        particles: &mut [Body<D>],
+        time: Float,
+        d_time: Float,
+        forces: impl Fn(&[Body<D>], Float) -> Vec<VectN<D>>,
+        magnetic_field: &MagneticField,
+    ) {
+        let forces_without_lorentz = forces;
+        let forces = |particles: &[Body<D>], time: Float| -> Vec<VectN<D>> {
+            let mut forces = forces_without_lorentz(particles, time);
+
+            if !magnetic_field.is_zero() {
+                for (f, p) in forces.iter_mut().zip(particles) {
+                    *f += p.lorentz_force(magnetic_field.at(plane(p.position)));
+                }
+            }
+
+            return forces;
+        };
+
+        match self {
+            Integrator::Euler => {
+                let accelerations = accelerations(particles, time, &forces);
+
+                for (p, a) in particles.iter_mut().zip(accelerations) {
+                    /* Zmiana położenia pole pod wykresem v(t); v rośnie liniowo. */
+                    p.position += d_time * p.velocity + a * d_time * d_time / 2.0;
+                    p.velocity += a * d_time;
+                    p.acceleration = a;
+                }
+            }
+
+            Integrator::VelocityVerlet => {
+                let old_accelerations = accelerations(particles, time, &forces);
+
+                for (p, a) in particles.iter_mut().zip(old_accelerations) {
+                    p.velocity += a * d_time / 2.0;
+                    p.position += p.velocity * d_time;
+                }
+
+                /* Siły w nowym położeniu. Siła Lorentza liczy się tu z prędkości z połowy
+                 * kroku, dlatego w polu magnetycznym lepsza jest metoda Borisa. */
+                let new_accelerations = accelerations(particles, time + d_time, &forces);
+
+                for (p, a) in particles.iter_mut().zip(new_accelerations) {
+                    p.velocity += a * d_time / 2.0;
+                    p.acceleration = a;
+                }
+            }
+
+            Integrator::Leapfrog => {
+                for p in particles.iter_mut() {
+                    p.position += p.velocity * d_time / 2.0;
+                }
+
+                let accelerations = accelerations(particles, time + d_time / 2.0, &forces);
+
+                for (p, a) in particles.iter_mut().zip(accelerations) {
+                    p.velocity += a * d_time;
+                    p.position += p.velocity * d_time / 2.0;
+                    p.acceleration = a;
+                }
+            }
+
+            Integrator::RungeKutta4 => {
+                /* Stan początkowy (położenie, prędkość) każdej cząsteczki. */
+                let initial: Vec<(VectN<D>, VectN<D>)> = particles.iter().map(|p| (p.position, p.velocity)).collect();
+                let mut state = particles.to_vec();
+
+                /* Każde k to pochodne stanu: (prędkość, przyspieszenie). */
+                let k1 = derivatives(&state, time, &forces);
+                advance(&mut state, &initial, &k1, d_time / 2.0);
+                let k2 = derivatives(&state, time + d_time / 2.0, &forces);
+                advance(&mut state, &initial, &k2, d_time / 2.0);
+                let k3 = derivatives(&state, time + d_time / 2.0, &forces);
+                advance(&mut state, &initial, &k3, d_time);
+                let k4 = derivatives(&state, time + d_time, &forces);
+
+                for (i, p) in particles.iter_mut().enumerate() {
+                    let d_position = (k1[i].0 + 2.0 * k2[i].0 + 2.0 * k3[i].0 + k4[i].0) / 6.0;
+                    let acceleration = (k1[i].1 + 2.0 * k2[i].1 + 2.0 * k3[i].1 + k4[i].1) / 6.0;
+
+                    p.position = initial[i].0 + d_position * d_time;
+                    p.velocity = initial[i].1 + acceleration * d_time;
+                    p.acceleration = acceleration;
+                }
+            }
+
+            Integrator::Boris => {
+                /* Tu potrzebujemy sił bez siły Lorentza, więc bierzemy oryginalne `forces`. */
+                let accelerations = accelerations(particles, time, &forces_without_lorentz);
+
+                for (p, a) in particles.iter_mut().zip(accelerations) {
+                    let old_velocity = p.velocity;
+
+                    /* Pół kroku przyspieszenia od pozostałych sił. */
+                    let v_minus = p.velocity + a * d_time / 2.0;
+
+                    /* Obrót o kąt q * B * dt / m wokół osi z. `t` to tangens połowy kąta, a `s`
+                     * dobrane tak, żeby długość wektora się nie zmieniła. Składowa wzdłuż z
+                     * (w 3D) się przy tym nie zmienia. */
+                    let t = p.charge * magnetic_field.at(plane(p.position)) * d_time / (2.0 * p.mass);
+                    let s = 2.0 * t / (1.0 + t * t);
+                    let v_prime = v_minus + cross_z(v_minus) * t;
+                    let v_plus = v_minus + cross_z(v_prime) * s;
+
+                    p.velocity = v_plus + a * d_time / 2.0;
+                    p.position += p.velocity * d_time;
+                    p.acceleration = (p.velocity - old_velocity) / d_time;
+                }
+            }
+        }
+    }
+}
+
+/* Przyspieszenia wszystkich cząsteczek w danym stanie.
+ *
+ * Nieruchome cząsteczki mają zerowe przyspieszenie, a prędkość też zawsze zerową, więc żadna
+ * z metod ich nie przesuwa (siła Lorentza przy zerowej prędkości też jest zerowa). */
+fn accelerations<const D: usize>(
+    particles: &[Body<D>],
+    time: Float,
+    forces: &impl Fn(&[Body<D>], Float) -> Vec<VectN<D>>,
+) -> Vec<VectN<D>> {
+    return forces(particles, time)
+        .iter()
+        .zip(particles)
+        .map(|(f, p)| if p.fixed { VectN::zeros() } else { f / p.mass })
+        .collect();
+}
+
+/* Pochodne stanu (prędkość, przyspieszenie) każdej cząsteczki, na potrzeby RK4. */
+fn derivatives<const D: usize>(
+    particles: &[Body<D>],
+    time: Float,
+    forces: &impl Fn(&[Body<D>], Float) -> Vec<VectN<D>>,
+) -> Vec<(VectN<D>, VectN<D>)> {
+    return particles
+        .iter()
+        .map(|p| p.velocity)
+        .zip(accelerations(particles, time, forces))
+        .collect();
+}
+
+/* Ustawia `state` na stan początkowy przesunięty o `d_time` wzdłuż pochodnych `k`. */
+fn advance<const D: usize>(state: &mut [Body<D>], initial: &[(VectN<D>, VectN<D>)], k: &[(VectN<D>, VectN<D>)], d_time: Float) {
+    for ((p, (position, velocity)), (d_position, d_velocity)) in state.iter_mut().zip(initial).zip(k) {
+        p.position = position + d_position * d_time;
+        p.velocity = velocity + d_velocity * d_time;
+    }
+}
+
+/* Ustawienia adaptacyjnego dzielenia kroku czasowego na podkroki.
+ *
+ * Czas jednej klatki (`stable_dt * time_multiplier`) przy bliskich spotkaniach cząsteczek
+ * jest o wiele za długi, więc dzielimy go na tyle podkroków, żeby w żadnym z nich
+ * przyspieszenie nie przesunęło cząsteczki o więcej niż `tolerance`. */
+#[derive(Clone, Copy, Debug)]
+pub struct AdaptiveTimestep {
+    pub enabled: bool,
+    /* Dopuszczalny lokalny błąd położenia w jednym podkroku, czyli a * dt^2 / 2. */
+    pub tolerance: Float,
+    /* Górne ograniczenie liczby podkroków w jednej klatce, żeby program się nie zawiesił. */
+    pub max_substeps: u32,
+}
+
+impl AdaptiveTimestep {
+    /* Długość następnego podkroku, gdy z całej klatki o długości `d_time` zostało jeszcze `remaining`.
+     *
+     * `accelerations` to wartości przyspieszeń cząsteczek z poprzedniego podkroku
+     * (`Particle::acceleration`), żeby nie liczyć sił dodatkowy raz. Przy zbliżaniu się
+     * cząsteczek przyspieszenie zmienia się płynnie, więc to w zupełności wystarcza. */
+    pub fn substep(&self, accelerations: impl Iterator<Item = Float>, d_time: Float, remaining: Float) -> Float {
+        if !self.enabled {
+            return remaining;
+        }
+
+        let max_acceleration = accelerations
+            .filter(|a| a.is_finite())
+            .fold(0.0, Float::max);
+
+        let preferred = if max_acceleration > 0.0 {
+            (2.0 * self.tolerance / max_acceleration).sqrt()
+        } else {
+            remaining
+        };
+
+        let min_d_time = d_time / self.max_substeps.max(1) as Float;
+
+        return preferred.max(min_d_time).min(remaining);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::particle::{consts, Particle, Vect};
+
+    /* Błąd położenia po czasie 1 dla cząsteczki w jednorodnym polu E(t) = sin(ωt) wzdłuż x,
+     * całkowanej w `steps` krokach. Dokładnie x(t) = (t - sin(ωt) / ω) / ω (q = m = 1). */
+    fn sine_field_error(integrator: Integrator, steps: u32) -> Float {
+        let omega = 5.0;
+        let mut particles = vec![Particle::new(0, 0.0, 0.0, 1.0, 1.0, 0.01)];
+        let d_time = 1.0 / steps as Float;
+
+        for i in 0..steps {
+            integrator.step(
+                &mut particles,
+                i as Float * d_time,
+                d_time,
+                |particles, time| particles.iter().map(|p| Vect::new(p.charge * (omega * time).sin(), 0.0)).collect(),
+                &MagneticField::NONE,
+            );
+        }
+
+        let exact = (1.0 - (omega * 1.0).sin() / omega) / omega;
+        return (particles[0].position.x - exact).abs();
+    }
+
+    /* Ile razy maleje błąd po dwukrotnym skróceniu kroku, czyli mniej więcej 2^rząd. */
+    fn convergence(integrator: Integrator) -> Float {
+        return sine_field_error(integrator, 10) / sine_field_error(integrator, 20);
+    }
+
+    #[test]
+    fn second_order_in_time_dependent_field() {
+        for integrator in [Integrator::VelocityVerlet, Integrator::Leapfrog] {
+            let ratio = convergence(integrator);
+            assert!(ratio > 3.0, "{}: {}", integrator.name(), ratio);
+        }
+    }
+
+    #[test]
+    fn runge_kutta_fourth_order_in_time_dependent_field() {
+        let ratio = convergence(Integrator::RungeKutta4);
+        assert!(ratio > 12.0, "{}", ratio);
+    }
+
+    /* Cząsteczka (q = m = 1) z prędkością (1, 0) w jednorodnym polu `bz`, bez innych sił,
+     * po `steps` krokach metody Borisa o długości `d_time`. */
+    fn gyrate(bz: Float, steps: u32, d_time: Float) -> Particle {
+        let mut particles = vec![Particle { velocity: Vect::new(1.0, 0.0), ..Particle::new(0, 0.5, 0.5, 1.0, 1.0, 0.01) }];
+        let field = MagneticField { uniform: bz, regions: vec![] };
+
+        for i in 0..steps {
+            Integrator::Boris.step(&mut particles, i as Float * d_time, d_time, |particles, _| vec![Vect::zeros(); particles.len()], &field);
+        }
+
+        return particles.remove(0);
+    }
+
+    #[test]
+    fn boris_preserves_speed() {
+        /* Nawet przy dużym kroku (ω dt = 0.5) obrót Borisa nie zmienia szybkości. */
+        let particle = gyrate(10.0, 10_000, 0.05);
+        assert!((particle.velocity.magnitude() - 1.0).abs() < 1e-3, "{}", particle.velocity.magnitude());
+    }
+
+    #[test]
+    fn boris_follows_cyclotron_orbit() {
+        /* Okres cyklotronowy 2π m / (q B), a promień m v / (q B) = 0.1. */
+        let bz = 10.0;
+        let steps = 400;
+        let period = 2.0 * consts::PI / bz;
+        let d_time = period / steps as Float;
+
+        /* Siła q v x B przy B wzdłuż +z skręca cząsteczkę zgodnie z ruchem wskazówek zegara,
+         * więc po ćwierci okresu prędkość jest skierowana w dół. */
+        let quarter = gyrate(bz, steps / 4, d_time);
+        assert!((quarter.velocity - Vect::new(0.0, -1.0)).magnitude() < 1e-2, "{:?}", quarter.velocity);
+
+        let full = gyrate(bz, steps, d_time);
+        assert!((full.position - Vect::new(0.5, 0.5)).magnitude() < 1e-3, "{:?}", full.position);
+        assert!((full.velocity - Vect::new(1.0, 0.0)).magnitude() < 1e-2, "{:?}", full.velocity);
+    }
+}
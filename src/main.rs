@@ -1,9 +1,38 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")] // hide console window on Windows in release
 #![allow(clippy::needless_return)]
-
+/* Rzutowania między `Float` a typami egui (f32, f64) przy jednej z precyzji nic nie robią,
+ * ale przy drugiej są potrzebne. */
+#![allow(clippy::unnecessary_cast)]
+
+mod bond;
+mod collision;
+mod drag;
+mod energy;
+mod force;
+mod integrator;
+mod momentum;
+mod obstacle;
+mod parameters;
 mod particle;
-
-use particle::{Particle, Vect};
+mod particle3d;
+mod pressure;
+mod projection;
+mod quadtree;
+mod thermostat;
+
+use bond::{Bond, BondKind};
+use energy::EnergyHistory;
+use force::{Coulomb, ForceLaw, Interactions};
+use integrator::{AdaptiveTimestep, Integrator};
+use momentum::{Impulses, MomentumHistory};
+use obstacle::Obstacle;
+use parameters::{CollisionMode, Domain, DragModel, GravityMode, Waveform, Limits, LengthUnit, MagneticRegion, MergePolicy, PairPotential, SimulationParameters, Softening, WallBehaviour};
+use particle::{consts, Body, Float, Particle, Vect, VectN};
+use particle3d::{Cube, Particle3D, Vect3};
+use pressure::PressureGauge;
+use projection::{Camera, Projection};
+use quadtree::{ForceBackend, QuadTree};
+use thermostat::{Thermostat, ThermostatKind};
 use rand::distributions::Uniform;
 use rand::prelude::*;
 extern crate nalgebra as na;
@@ -11,7 +40,7 @@ extern crate nalgebra as na;
 use eframe::{
     egui::{
         self,
-        plot::{Bar, BarChart, Plot, Points},
+        plot::{Bar, BarChart, MarkerShape, Plot, Points},
     },
     epaint::Color32,
 };
@@ -26,14 +55,138 @@ fn main() {
 
 
 
-/* Co ma się dziać przy kliknięciu: dodawanie cząsteczek lub śledzenie zaznaczonej cząsteczki. */
-enum ClickAction { Add, Track }
+/* Co ma się dziać przy kliknięciu: dodawanie cząsteczek, śledzenie zaznaczonej cząsteczki,
+ * łączenie dwóch kolejno klikniętych cząsteczek wiązaniem, rysowanie przeszkody
+ * lub przypinanie (i odpinanie) cząsteczki w miejscu. */
+#[derive(PartialEq)]
+enum ClickAction { Add, Track, Bond, Obstacle, Pin }
+
+/* Symulacja na płaszczyźnie albo w sześcianie 3D. Fizyka obu trybów jest ta sama,
+ * ale każdy tryb ma swój `World`. Przeszkody rysuje się tylko w 2D (w 3D działają
+ * jako graniastosłupy wzdłuż osi z). */
+#[derive(Clone, Copy, PartialEq)]
+enum Mode { Plane, Space }
+
+/* Cząsteczki jednego trybu i wszystko, co jest z nimi związane. Obszarem, w którym się
+ * poruszają, jest `Limits` w 2D albo `Cube` w 3D. */
+struct World<const D: usize> {
+    particles: Vec<Body<D>>,
+    /* Sprężyny i sztywne wiązania między cząsteczkami. */
+    bonds: Vec<Bond>,
+    /* Siły liczone w każdym kroku symulacji. */
+    interactions: Interactions<D>,
+    /* Ile cząsteczek pochłonęła każda ze ścian (w kolejności jak w `Domain::wall`). */
+    absorbed: Vec<u32>,
+    /* Popęd przekazany każdej ze ścian przy odbiciach w ostatniej klatce. */
+    wall_impulses: Vec<Float>,
+}
+
+impl<const D: usize> World<D> {
+    fn new() -> Self {
+        return Self {
+            particles: vec![],
+            bonds: vec![],
+            interactions: Interactions::builtin(),
+            absorbed: vec![0; 2 * D],
+            wall_impulses: vec![0.0; 2 * D],
+        };
+    }
+
+    /* Usuwa wszystkie cząsteczki i wiązania. */
+    fn clear(&mut self) {
+        self.particles.clear();
+        self.bonds.clear();
+        self.absorbed.fill(0);
+    }
+
+    /* Dodawanie cząsteczek przez kliknięcie myszką w punkcie `position`. */
+    fn add_user_particles(
+        &mut self,
+        domain: &impl Domain<D>,
+        position: VectN<D>,
+        input_state: UserParticleInputState,
+        bond_input_state: &UserBondInputState,
+        next_particle_id: &mut u32,
+        rng: &mut impl Rng,
+    ) {
+        /* ID poprzedniej wstawionej cząsteczki, do łączenia w łańcuch. */
+        let mut previous_id = None;
+
+        for i in 0..input_state.count {
+            let offset = if input_state.chain {
+                /* Łańcuch układamy wzdłuż osi x, ze środkiem pod kursorem. */
+                let spacing = 3.0 * input_state.radius;
+                let x = (i as Float - (input_state.count - 1) as Float / 2.0) * spacing;
+                VectN::from_fn(|axis, _| if axis == 0 { x } else { 0.0 })
+            } else {
+                /* Jeśli jedna cząsteczka, wstawiamy ją dokładnie tam, gdzie jest kursor. 
+                 * Jeśli więcej, to dodajemy pewien rozrzut w losowym kierunku, bo inaczej
+                 * wszystkie by się pokryły. */
+                let spread = Body::<D>::SPAWN_SPREAD * domain.extent();
+                let radius = rng.sample(Uniform::new(0.0, 1.0)) * u32::min(input_state.count - 1, 1) as Float * spread;
+                let direction = VectN::<D>::from_fn(|_, _| particle::standard_normal(rng)).normalize();
+                direction * radius
+            };
+
+            /* Przy naprzemiennych ładunkach z dwóch cząsteczek wychodzi dipol. */
+            let charge = if input_state.alternate_charges && i % 2 == 1 { -input_state.charge } else { input_state.charge };
+
+            if Body::valid(position + offset, charge, input_state.mass, input_state.radius, domain) {
+                let particle = Body {
+                    fixed: input_state.fixed,
+                    ..Body::from_position(*next_particle_id, position + offset, charge, input_state.mass, input_state.radius)
+                };
+
+                if input_state.chain {
+                    if let Some(previous) = previous_id.and_then(|id| self.particles.iter().find(|p| p.id == id)) {
+                        self.bonds.push(bond_input_state.bond(previous, &particle, domain));
+                    }
+                    previous_id = Some(particle.id);
+                }
+
+                self.particles.push(particle);
+                *next_particle_id += 1;
+            }
+        }
+    }
+
+    /* Łączy wiązaniem cząsteczki o ID `a` i `b`, jeśli obie istnieją. */
+    fn add_bond(&mut self, a: u32, b: u32, bond_input_state: &UserBondInputState, domain: &impl Domain<D>) {
+        let a = self.particles.iter().find(|p| p.id == a);
+        let b = self.particles.iter().find(|p| p.id == b);
+        if let (Some(a), Some(b)) = (a, b) {
+            self.bonds.push(bond_input_state.bond(a, b, domain));
+        }
+    }
+
+    /* Przypina albo odpina cząsteczkę o ID `id`. */
+    fn toggle_fixed(&mut self, id: u32) {
+        if let Some(p) = self.particles.iter_mut().find(|p| p.id == id) {
+            p.fixed = !p.fixed;
+            p.velocity = VectN::zeros();
+            p.acceleration = VectN::zeros();
+        }
+    }
+}
+
+impl<const D: usize> Default for World<D> {
+    fn default() -> Self {
+        return Self::new();
+    }
+}
 
 struct MyEguiApp {
-    particles: Vec<Particle>,
-    time_multiplier: f32,
-    velocity_precision: f32,
-    energy_precision: f32,
+    mode: Mode,
+    /* Cząsteczki trybu 2D i trybu 3D. */
+    plane: World<2>,
+    space: World<3>,
+    /* Sześcian ograniczający ruch cząsteczek w trybie 3D. */
+    cube: Cube,
+    /* Kamera widoku perspektywicznego w trybie 3D. */
+    camera: Camera,
+    time_multiplier: Float,
+    velocity_precision: Float,
+    energy_precision: Float,
     rng: ThreadRng,
     /* Parametry wstawiania nowych cząsteczek myszką. */
     user_particle_input_state: UserParticleInputState,
@@ -42,12 +195,48 @@ struct MyEguiApp {
     /* Aktualnie śledzona cząsteczka. */
     tracked_particle: Option<TrackedParticle>,
     click_action: ClickAction,
+    /* Parametry nowych wiązań. */
+    user_bond_input_state: UserBondInputState,
+    /* Pierwsza kliknięta cząsteczka nowego wiązania. */
+    bond_start: Option<u32>,
+    /* Wierzchołki rysowanej przeszkody. */
+    obstacle_draft: Vec<Vect>,
+    /* Metoda całkowania ruchu używana w `simulation`. */
+    integrator: Integrator,
+    adaptive_timestep: AdaptiveTimestep,
+    thermostat: Thermostat,
+    /* Na ile podkroków została podzielona ostatnia klatka. */
+    substeps: u32,
+    /* Czas, który upłynął w symulacji (potrzebny dla pól zależnych od czasu). */
+    time: Float,
+    /* Sposób liczenia sił elektrostatycznych (w symulacji i na wykresie pola). */
+    force_backend: ForceBackend,
+    /* Kąt otwarcia dla drzewa Barnesa-Huta. */
+    opening_angle: Float,
+    /* Stałe fizyczne, edytowalne w trakcie symulacji. */
+    parameters: SimulationParameters,
+    /* Indeks ostatnio wybranego zestawu z `SimulationParameters::presets` razem z parametrami
+     * zaraz po jego wybraniu. Po zmianie któregokolwiek parametru zestaw nie jest już wybrany. */
+    preset: Option<(usize, SimulationParameters)>,
+    /* Jednostka, w której pokazujemy długości. */
+    length_unit: LengthUnit,
+    /* Energia rozproszona przez opór od początku symulacji. */
+    dissipated: Float,
+    energy_history: EnergyHistory,
+    /* Pęd przekazany przez ściany, opór i między cząsteczkami od początku symulacji. */
+    impulses: Impulses,
+    momentum_history: MomentumHistory,
+    pressure_gauge: PressureGauge,
 }
 
 impl MyEguiApp {
     fn new(_cc: &eframe::CreationContext<'_>) -> Self {
         return Self {
-            particles: vec![],
+            mode: Mode::Plane,
+            plane: World::new(),
+            space: World::new(),
+            cube: Cube::default(),
+            camera: Camera::new(),
             time_multiplier: 1.0,
             velocity_precision: 0.2,
             energy_precision: 1.0,
@@ -56,538 +245,1766 @@ impl MyEguiApp {
                 count: 1,
                 charge: 0.5,
                 mass: 0.5,
+                radius: 0.015,
+                chain: false,
+                alternate_charges: false,
+                fixed: false,
             },
             next_particle_id: 0,
             tracked_particle: None,
             click_action: ClickAction::Add,
+            user_bond_input_state: UserBondInputState {
+                rigid: false,
+                stiffness: 200.0,
+                damping: 0.5,
+            },
+            bond_start: None,
+            obstacle_draft: vec![],
+            integrator: Integrator::VelocityVerlet,
+            adaptive_timestep: AdaptiveTimestep {
+                enabled: true,
+                tolerance: 0.0001,
+                max_substeps: 200,
+            },
+            thermostat: Thermostat::new(ThermostatKind::None, 1.0, 0.5, 0),
+            substeps: 0,
+            time: 0.0,
+            force_backend: ForceBackend::BarnesHut,
+            opening_angle: 0.5,
+            parameters: SimulationParameters::default(),
+            /* Na starcie parametry są takie jak po wybraniu zestawu "Domyślne". */
+            preset: Some((0, SimulationParameters::default())),
+            length_unit: LengthUnit::None,
+            dissipated: 0.0,
+            energy_history: EnergyHistory::new(),
+            impulses: Impulses::default(),
+            momentum_history: MomentumHistory::new(),
+            pressure_gauge: PressureGauge::new(1.0),
         };
     }
 
-    fn simulation(&mut self, d_time: f32) {
-        /* Wypadkowe siły dla każdej cząsteczki w wektorze `particles`.
-         *
-         * Przy przekazywaniu cząsteczek do `net_electrostatic_force` musimy wyrzucić tą, dla
-         * której liczymy siłę, żeby nie liczyć oddziaływania elektrostatycznego niej samej ze sobą. */
-        let forces = self
-            .particles
+    fn simulation(&mut self, d_time: Float) {
+        /* `simulate` potrzebuje i świata, i reszty stanu aplikacji, więc świat na chwilę wyjmujemy. */
+        match self.mode {
+            Mode::Plane => {
+                let (mut world, limits, obstacles) = (std::mem::take(&mut self.plane), self.parameters.limits, self.parameters.obstacles.clone());
+                self.simulate(&mut world, &limits, &obstacles, d_time);
+                self.plane = world;
+            }
+            Mode::Space => {
+                let (mut world, cube, obstacles) = (std::mem::take(&mut self.space), self.cube, self.space_obstacles());
+                self.simulate(&mut world, &cube, &obstacles, d_time);
+                self.space = world;
+            }
+        }
+    }
+
+    /* Przeszkody w trybie 3D. Rysuje się je w obszarze 2D, więc przenosimy je z niego na ścianę
+     * xy sześcianu (dalej biegną wzdłuż z przez cały sześcian). */
+    fn space_obstacles(&self) -> Vec<Obstacle> {
+        let (min, max) = self.parameters.limits.bounds();
+        let (cube_min, cube_max) = self.cube.bounds();
+
+        return self.parameters.obstacles
             .iter()
-            .map(|p| {
-                p.net_electrostatic_force(
-                    self.particles
-                        .iter()
-                        .filter(|p2| p.id != p2.id)
-                ) + p.gravitational_force()
-            })
-            .collect::<Vec<_>>();
+            .map(|obstacle| obstacle.mapped((min, max), (particle::plane(cube_min), particle::plane(cube_max))))
+            .collect();
+    }
 
-        self.particles
-            .iter_mut()
-            .zip(forces.iter())
-            .for_each(|(p, f)| p.apply_force(*f, d_time));
+    /* Jedna klatka symulacji trwająca `d_time`, dla cząsteczek z `world` w obszarze `domain`
+     * z przeszkodami `obstacles` (już we współrzędnych tego obszaru). */
+    fn simulate<const D: usize>(&mut self, world: &mut World<D>, domain: &impl Domain<D>, obstacles: &[Obstacle], d_time: Float) {
+        /* Dzielimy klatkę na podkroki (albo nie, jeśli adaptacyjny krok jest wyłączony). */
+        let mut remaining = d_time;
+        self.substeps = 0;
+        world.wall_impulses.fill(0.0);
+
+        while remaining > 0.0 && self.substeps < self.adaptive_timestep.max_substeps {
+            let sub_d_time = self.adaptive_timestep.substep(world.particles.iter().map(|p| p.acceleration.magnitude()), d_time, remaining);
+            let previous_positions: Vec<VectN<D>> = world.particles.iter().map(|p| p.position).collect();
+
+            /* Popęd od sił par liczymy z sił pierwszego etapu integratora (żeby nie liczyć ich
+             * drugi raz), a resztę zmiany pędu w tym kroku przypisujemy polom zewnętrznym. */
+            let momentum_before = momentum::linear_momentum(&world.particles);
+            let first_pair_forces = std::cell::RefCell::new(None);
+
+            let (params, backend, theta) = (&self.parameters, self.force_backend, self.opening_angle);
+            let (interactions, bonds) = (&world.interactions, &world.bonds);
+            self.integrator.step(
+                &mut world.particles,
+                self.time,
+                sub_d_time,
+                |particles, time| {
+                    let mut forces = interactions.pair_forces(particles, params, domain, backend, theta);
+                    bond::add_bond_forces(bonds, particles, domain, &mut forces);
+                    first_pair_forces.borrow_mut().get_or_insert_with(|| forces.clone());
+                    interactions.add_external_forces(particles, params, time, &mut forces);
+                    forces
+                },
+                &params.magnetic_field,
+            );
+
+            let pair_impulse = self.add_pair_impulses(world, first_pair_forces.into_inner().unwrap_or_default(), sub_d_time);
+            self.impulses.external += momentum::linear_momentum(&world.particles) - momentum_before - pair_impulse;
+
+            let momentum_before = momentum::linear_momentum(&world.particles);
+            self.dissipated += world.interactions.exact_steps(&mut world.particles, &self.parameters, sub_d_time);
+            self.impulses.drag += momentum::linear_momentum(&world.particles) - momentum_before;
+
+            let momentum_before = momentum::linear_momentum(&world.particles);
+            self.thermostat.apply(&mut world.particles, sub_d_time);
+            obstacle::resolve_obstacles(obstacles, &mut world.particles, &previous_positions);
+            self.impulses.external += momentum::linear_momentum(&world.particles) - momentum_before;
+
+            /* Zderzenia i sztywne wiązania między ruchomymi cząsteczkami zachowują pęd,
+             * więc zmienia się on tu tylko przez przypięte cząsteczki. */
+            let momentum_before = momentum::linear_momentum(&world.particles);
+            bond::apply_constraints(&world.bonds, &mut world.particles, domain);
+            let merges = collision::resolve_collisions(&mut world.particles, &self.parameters, domain, &mut self.next_particle_id);
+            self.impulses.constraints += momentum::linear_momentum(&world.particles) - momentum_before;
+
+            if !merges.is_empty() {
+                bond::remove_dangling(&mut world.bonds, &world.particles);
+            }
 
-        /* Zapisujemy dane śledzonej cząsteczki z tej instancji symulacji do narysowania wykresów. */
-        if let Some(ref mut tracked_particle) = self.tracked_particle {
-            if let Some(particle) = self.particles.iter().find(|p| p.id == tracked_particle.id) {
-                if tracked_particle.path.len() == TrackedParticle::DATA_POINT_COUNT_PATH {
-                    tracked_particle.path.pop_front();
+            /* Jeśli śledzona cząsteczka się z czymś połączyła, to śledzimy dalej tę połączoną. */
+            if let Some(ref mut tracked_particle) = self.tracked_particle {
+                if let Some(merge) = merges.iter().find(|m| m.removed.contains(&tracked_particle.id)) {
+                    tracked_particle.id = merge.created;
                 }
+            }
 
-                if tracked_particle.velocity.len() == TrackedParticle::DATA_POINT_COUNT_VELOCITY {
-                    tracked_particle.velocity.pop_front();
+            let momentum_before = momentum::linear_momentum(&world.particles);
+            let (rng, absorbed, wall_impulses) = (&mut self.rng, &mut world.absorbed, &mut world.wall_impulses);
+            let count = world.particles.len();
+            world.particles.retain_mut(|p| match p.apply_limits(domain, rng, wall_impulses) {
+                Some(wall) => {
+                    absorbed[wall] += 1;
+                    false
                 }
+                None => true,
+            });
 
-                if tracked_particle.acceleration.len() == TrackedParticle::DATA_POINT_COUNT_ACCELERATION {
-                    tracked_particle.acceleration.pop_front();
-                }
+            self.impulses.walls += momentum::linear_momentum(&world.particles) - momentum_before;
 
-                tracked_particle.path.push_back(particle.position);
-                tracked_particle.velocity.push_back(particle.velocity);
-                tracked_particle.acceleration.push_back(particle.acceleration);
+            if world.particles.len() != count {
+                bond::remove_dangling(&mut world.bonds, &world.particles);
             }
+
+            remaining -= sub_d_time;
+            self.time += sub_d_time;
+            self.substeps += 1;
         }
-    }
 
-    /* Dodawanie cząsteczek przez kliknięcie myszką. */
-    fn add_user_particles(&mut self, x: f32, y: f32, input_state: UserParticleInputState) {
-        for _ in 0..input_state.count {
-            /* Jeśli jedna cząsteczka, wstawiamy ją dokładnie tam, gdzie jest kursor. 
-             * Jeśli więcej, to dodajemy pewien rozrzut, bo inaczej wszystkie by się pokryły. */
-            let radius = self.rng.sample(Uniform::new(0.0, 1.0)) * u32::min(input_state.count - 1, 1) as f32 * 0.1;
-            let angle = self.rng.sample(Uniform::new(0.0, std::f32::consts::PI * 2.0));
-            let dx = radius * f32::cos(angle);
-            let dy = radius * f32::sin(angle);
-
-            if Particle::valid(x + dx, y + dy, input_state.charge, input_state.mass) {
-                self.particles.push(Particle::new(self.next_particle_id, x + dx, y + dy, input_state.charge, input_state.mass));
-                self.next_particle_id += 1;
+        /* Zapisujemy dane śledzonej cząsteczki z tej instancji symulacji do narysowania wykresów. */
+        if let Some(ref mut tracked_particle) = self.tracked_particle {
+            if let Some(particle) = world.particles.iter().find(|p| p.id == tracked_particle.id) {
+                tracked_particle.record(
+                    particle.position.fixed_resize::<3, 1>(0.0),
+                    particle.velocity.magnitude(),
+                    particle.acceleration.magnitude(),
+                );
             }
         }
+
+        self.record_energy(world, domain);
+        self.record_momentum(world, domain);
+        self.record_pressure(world, domain, d_time - remaining);
     }
-}
 
-impl eframe::App for MyEguiApp {
-    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
-        /* Potrzebne do obliczania współrzędnych przy dodawaniu cząsteczek myszką:
-         * wartości współrzędnych kursora w układzie współrzędnych wykresu.*/
-        let mut particle_plot_pointer_coordinates = None;
+    /* Zapisuje składowe energii z aktualnej chwili do `energy_history`. */
+    fn record_energy<const D: usize>(&mut self, world: &World<D>, domain: &impl Domain<D>) {
+        /* Przy zatrzymanej symulacji czas stoi i nie ma czego dopisywać. */
+        if self.substeps == 0 {
+            return;
+        }
 
-        /* Id cząsteczki aktualnie pod kursorem (może być inna niż aktualnie śledzona). */
-        let mut selected_particle_id: Option<u32> = None;
+        let mut components = world.interactions.potential_energies(&world.particles, &self.parameters, domain, self.time);
+        components.insert(0, ("Kinetyczna", energy::kinetic_energy(&world.particles)));
 
-        egui::CentralPanel::default().show(ctx, |ui| {
-            /* Wielkość okienka z symulacją. */
-            let simulation_plot_size = 500.0;
-            /* Wielkość pozostałych wykresów. */
-            let plot_size = 200.0;
+        if !world.bonds.is_empty() {
+            components.push(("Wiązania", bond::potential_energy(&world.bonds, &world.particles, domain)));
+        }
+
+        if self.parameters.drag_model != DragModel::None {
+            components.push(("Rozproszona przez opór", self.dissipated));
+        }
+
+        self.energy_history.record(self.time, &components, world.particles.len());
+    }
+
+    /* Dolicza do `impulses` wymianę pędu między cząsteczkami z `world` w podkroku trwającym
+     * `d_time`, z sił par `pair_forces` (razem z wiązaniami) policzonych przez integrator.
+     * Zwraca popęd, który siły par przekazały ruchomym cząsteczkom. */
+    fn add_pair_impulses<const D: usize>(&mut self, world: &World<D>, pair_forces: Vec<VectN<D>>, d_time: Float) -> Vect3 {
+        return self.impulses.add_pair_forces(
+            pair_forces.into_iter().zip(&world.particles).map(|(f, p)| (f.fixed_resize::<3, 1>(0.0), p.fixed)),
+            d_time,
+        );
+    }
+
+    /* Zapisuje pęd, moment pędu i popędy do `momentum_history`. */
+    fn record_momentum<const D: usize>(&mut self, world: &World<D>, domain: &impl Domain<D>) {
+        if self.substeps == 0 {
+            return;
+        }
+
+        let linear = momentum::linear_momentum(&world.particles);
+        let angular = momentum::angular_momentum(&world.particles, domain);
+        self.momentum_history.record(self.time, linear, angular, &self.impulses);
+    }
+
+    /* Zapisuje do `pressure_gauge` popęd przekazany ścianom w klatce trwającej `d_time`
+     * razem z temperaturą i liczbą ruchomych cząsteczek. */
+    fn record_pressure<const D: usize>(&mut self, world: &World<D>, domain: &impl Domain<D>, d_time: Float) {
+        if self.substeps == 0 {
+            return;
+        }
+
+        let bounces = |wall: parameters::Wall| matches!(wall.behaviour, WallBehaviour::Reflect | WallBehaviour::Thermal);
+        let areas: Vec<Float> = (0..2 * D)
+            .map(|wall| if bounces(domain.wall(wall)) { domain.wall_area(wall / 2) } else { 0.0 })
+            .collect();
+
+        self.pressure_gauge.record(
+            self.time,
+            d_time,
+            &world.wall_impulses,
+            &areas,
+            thermostat::kinetic_temperature(&world.particles),
+            world.particles.iter().filter(|p| !p.fixed).count(),
+        );
+    }
+
+    /* Zeruje czas symulacji, liczniki i wykresy diagnostyczne (energia, pęd, ciśnienie). */
+    fn reset_diagnostics(&mut self) {
+        self.time = 0.0;
+        self.substeps = 0;
+        self.dissipated = 0.0;
+        self.plane.absorbed.fill(0);
+        self.space.absorbed.fill(0);
+        self.energy_history.clear();
+        self.impulses = Impulses::default();
+        self.momentum_history.clear();
+        self.pressure_gauge.clear();
+    }
 
-            ui.horizontal(|ui| {
+    /* Kolor cząsteczki na wykresach symulacji.
+     *
+     * Kolor jest skalowany do dozwolonego przedziału ładunku (opisanego w `Particle::new`),
+     * a nie do maksymalnego ładunku wśród wszystkich cząsteczek, bo jak dodajemy nowe
+     * cząsteczki w trakcie symulacji, to trzeba by było wszystko od nowa przeliczać,
+     * jeśli zmieni się maksimum. */
+    fn particle_color(&self, id: u32, charge: Float, selected_particle_id: Option<u32>) -> Color32 {
+        /* Śledzona cząsteczka ma się wyróżniać. */
+        return if self.tracked_particle.as_ref().is_some_and(|tracked| tracked.id == id) {
+            Color32::from_rgb(0, 255, 0)
+        /* Zaznaczona też. */
+        } else if selected_particle_id == Some(id) {
+            Color32::from_rgb(255, 255, 0)
+        } else if charge >= 0.0 {
+            /* "Casting from a float to an integer will round the float towards zero". */
+            Color32::from_rgb(255, 255 - (255.0 * charge) as u8, 255 - (255.0 * charge) as u8)
+        } else {
+            Color32::from_rgb(255 - (-255.0 * charge) as u8, 255 - (-255.0 * charge) as u8, 255)
+        };
+    }
+
+    /* Wykresy trybu 3D: widok perspektywiczny z krążącej kamery i pod nim trzy rzuty
+     * prostokątne na płaszczyzny XY, XZ i YZ.
+     *
+     * Ustawia `selected_particle_id` na cząsteczkę pod kursorem (na dowolnym z wykresów)
+     * i zwraca punkt sześcianu pod kursorem na którymś z rzutów, z brakującą współrzędną
+     * w środku sześcianu. */
+    fn space_plots(&mut self, ui: &mut egui::Ui, size: f32, selected_particle_id: &mut Option<u32>) -> Option<Vect3> {
+        if self.camera.orbit {
+            self.camera.yaw += self.camera.orbit_speed * ui.input().stable_dt as Float;
+        }
+
+        let (cube, camera) = (self.cube, self.camera);
+        let obstacles = self.space_obstacles();
+        let empty_path = std::collections::VecDeque::new();
+        let tracked_path = self.tracked_particle.as_ref().map_or(&empty_path, |tracked| &tracked.path);
+        let tracked_color = Color32::from_rgb(0, 160, 0);
+        let mut pointer_position = None;
+
+        ui.heading("Symulacja");
+
+        /* Kamera patrzy na środek sześcianu, więc rzut mieści się w mniej więcej ± boku. */
+        let extent = cube.size as f64;
+        let perspective = Plot::new("perspective")
+            .data_aspect(1.0)
+            .width(size)
+            .height(size)
+            .allow_drag(false)
+            .allow_scroll(false)
+            .allow_zoom(false)
+            .allow_boxed_zoom(false)
+            .include_x(-extent)
+            .include_x(extent)
+            .include_y(-extent)
+            .include_y(extent)
+            .show_axes([false, false])
+            .show(ui, |plot_ui| {
+                let project = |position| camera.project(position, &cube);
+                let pixels_per_unit = pixels_per_unit(plot_ui);
+
+                /* `pointer_coordinate` zwraca kursor także wtedy, gdy jest nad innym wykresem,
+                 * więc bierzemy go tylko z wykresu, nad którym naprawdę jest. */
+                if let Some(pointer) = plot_ui.pointer_coordinate().filter(|_| plot_ui.plot_hovered()) {
+                    *selected_particle_id = particle_under_pointer(
+                        self.space.particles.iter().filter_map(|p| Some((p.id, project(p.position)?.0))),
+                        pointer,
+                        pixels_per_unit,
+                    );
+                }
+
+                draw_cube(plot_ui, |position| project(position).map(|(point, _)| point), &cube);
+
+                for segment in path_segments(tracked_path, |r| cube.minimum_image(r) != r, |position| project(position).map(|(point, _)| point)) {
+                    plot_ui.line(egui::widgets::plot::Line::new(egui::widgets::plot::PlotPoints::from(segment)).color(tracked_color));
+                }
+
+                draw_bonds(plot_ui, &self.space, &cube, |position| project(position).map(|(point, _)| point));
+
+                /* Dalsze cząsteczki (czyli mniejsze) rysujemy pierwsze, żeby bliższe je zasłaniały. */
+                let mut visible: Vec<(&Particle3D, [f64; 2], Float)> = self.space.particles
+                    .iter()
+                    .filter_map(|p| project(p.position).map(|(point, scale)| (p, point, scale)))
+                    .collect();
+                visible.sort_by(|a, b| a.2.total_cmp(&b.2));
+
+                for (p, point, scale) in visible {
+                    let radius = ((p.radius * scale) as f32 * pixels_per_unit).max(2.0);
+                    draw_particle(plot_ui, point, radius, self.particle_color(p.id, p.charge, *selected_particle_id), p.fixed);
+                }
+            })
+            .response;
+
+        /* Przeciąganie po widoku obraca kamerę. */
+        if perspective.dragged() {
+            let delta = perspective.drag_delta();
+            self.camera.yaw -= delta.x as Float * 0.01;
+            self.camera.pitch = (self.camera.pitch + delta.y as Float * 0.01).clamp(*Camera::PITCH_RANGE.start(), *Camera::PITCH_RANGE.end());
+        }
+
+        ui.horizontal(|ui| {
+            for projection in Projection::ALL {
                 ui.vertical(|ui| {
-                    {
-                        /* Cząsteczki. */
-                        let markers_plot = Plot::new("markers_demo")
-                            .view_aspect(1.0)
-                            .width(simulation_plot_size)
-                            .height(simulation_plot_size)
-                            .allow_drag(false)
-                            .allow_scroll(false)
-                            .allow_zoom(false)
-                            .allow_boxed_zoom(false)
-                            .include_x(0.0)
-                            .include_x(1.0)
-                            .include_y(0.0)
-                            .include_y(1.0);
-
-                        ui.heading("Symulacja");
-
-                        markers_plot.show(ui, |plot_ui| {
-                            particle_plot_pointer_coordinates = plot_ui.pointer_coordinate();
-
-                            /* Szukamy indeksu cząsteczki pod kursorem. */
-                            if let Some(particle_plot_pointer_coordinates) = particle_plot_pointer_coordinates {
-                                let particle_plot_pointer_coordinates = na::Vector2::<f64>::new(particle_plot_pointer_coordinates.x, particle_plot_pointer_coordinates.y);
-
-                                /* Jeśli kursor znajduje się w co najwyżej takiej odległości od środka
-                                 * cząsteczki, to uznajemy, że jest na cząsteczce. */
-                                let selection_radius = 0.0235;
-
-                                /* Szukamy cząsteczki najbliżej kursora i patrzymy, czy jest w promieniu. */
-                                selected_particle_id = self.particles.iter()
-                                    .map(|p| (p.id, (p.position.cast::<f64>() - particle_plot_pointer_coordinates).magnitude()))
-                                    .min_by(|(_, d1), (_, d2)| d1.total_cmp(d2))
-                                    .filter(|(_, d)| d <= &selection_radius)
-                                    .map(|(id, _)| id);
+                    ui.label(projection.name());
+
+                    let projection_size = size / 3.0 - 6.0;
+                    Plot::new(("projection", projection.name()))
+                        .data_aspect(1.0)
+                        .width(projection_size)
+                        .height(projection_size)
+                        .allow_drag(false)
+                        .allow_scroll(false)
+                        .allow_zoom(false)
+                        .allow_boxed_zoom(false)
+                        .include_x(cube.min)
+                        .include_x(cube.max())
+                        .include_y(cube.min)
+                        .include_y(cube.max())
+                        .show_axes([false, false])
+                        .show(ui, |plot_ui| {
+                            let pixels_per_unit = pixels_per_unit(plot_ui);
+                            let [horizontal, vertical] = projection.axes();
+
+                            if let Some(pointer) = plot_ui.pointer_coordinate().filter(|_| plot_ui.plot_hovered()) {
+                                *selected_particle_id = particle_under_pointer(
+                                    self.space.particles.iter().map(|p| (p.id, projection.project(p.position))),
+                                    pointer,
+                                    pixels_per_unit,
+                                );
 
+                                let mut position = cube.center();
+                                position[horizontal] = pointer.x as Float;
+                                position[vertical] = pointer.y as Float;
+                                pointer_position = Some(position);
                             }
 
+                            plot_ui.polygon(
+                                egui::widgets::plot::Polygon::new(egui::widgets::plot::PlotPoints::from(vec![
+                                    [cube.min as f64, cube.min as f64],
+                                    [cube.max() as f64, cube.min as f64],
+                                    [cube.max() as f64, cube.max() as f64],
+                                    [cube.min as f64, cube.max() as f64],
+                                ]))
+                                .color(Color32::GRAY)
+                                .fill_alpha(0.0)
+                            );
 
-                            for p in &self.particles {
-                                /* Dozwolone przedziały masy i ładunku są opisane w Particle::new(). */
-
-                                /* Kolor jest skalowany do dozwolonego przedziału ładunku, a nie do
-                                 * maksymalnego ładunku wśród wszystkich cząsteczek, bo jak dodajemy
-                                 * nowe cząsteczki w trakcie symulacji, to trzeba by było wszystko od
-                                 * nowa przeliczać jeśli zmieni się maksimum. */
-                                let color_value = 
-                                    /* Śledzona cząsteczka ma się wyróżniać. */
-                                    if self.tracked_particle.is_some() && self.tracked_particle.as_ref().unwrap().id == p.id {
-                                        Color32::from_rgb(0, 255, 0)
-                                    /* Zaznaczona też. */
-                                    } else if selected_particle_id.is_some() && selected_particle_id.unwrap() == p.id {
-                                        Color32::from_rgb(255, 255, 0)
-                                    } else if p.charge >= 0.0 {
-                                        /* "Casting from a float to an integer will round the float towards zero". */
-                                        Color32::from_rgb(255, 255 - (255.0 * p.charge) as u8, 255 - (255.0 * p.charge) as u8)
-                                    } else {
-                                        Color32::from_rgb(255 - (-255.0 * p.charge) as u8, 255 - (-255.0 * p.charge) as u8, 255)
-                                    };
-
-                                /* Analogiczny komentarz jak dla ładunku. */
-                                let radius = 7.0 * p.mass + 3.0;
-                                plot_ui.points(
-                                    Points::new([p.position.x as f64, p.position.y as f64])
-                                        .radius(radius)
-                                        .color(color_value),
-                                );
+                            /* Przeszkody są graniastosłupami wzdłuż z, więc z góry widać ich przekrój. */
+                            if projection == Projection::XY {
+                                draw_obstacles(plot_ui, &obstacles);
                             }
 
+                            for segment in path_segments(tracked_path, |r| cube.minimum_image(r) != r, |position| Some(projection.project(position))) {
+                                plot_ui.line(egui::widgets::plot::Line::new(egui::widgets::plot::PlotPoints::from(segment)).color(tracked_color));
+                            }
+
+                            draw_bonds(plot_ui, &self.space, &cube, |position| Some(projection.project(position)));
+
+                            /* Cząsteczki o większej trzeciej współrzędnej są na wierzchu. */
+                            let depth_axis = 3 - horizontal - vertical;
+                            let mut particles: Vec<&Particle3D> = self.space.particles.iter().collect();
+                            particles.sort_by(|a, b| a.position[depth_axis].total_cmp(&b.position[depth_axis]));
+
+                            for p in particles {
+                                let radius = (p.radius as f32 * pixels_per_unit).max(2.0);
+                                let color = self.particle_color(p.id, p.charge, *selected_particle_id);
+                                draw_particle(plot_ui, projection.project(p.position), radius, color, p.fixed);
+                            }
                         });
+                });
+            }
+        });
+
+        ui.horizontal(|ui| {
+            ui.checkbox(&mut self.camera.orbit, "Obracanie kamery");
+            ui.add_enabled(
+                self.camera.orbit,
+                egui::Slider::new(&mut self.camera.orbit_speed, -2.0..=2.0).text("Prędkość").fixed_decimals(2)
+            );
+        });
+        ui.label("Widok można obracać, przeciągając go myszką");
+
+        return pointer_position;
+    }
+
+    /* Opcje symulacji i parametry pod wykresem symulacji. */
+    fn options_panel(&mut self, ui: &mut egui::Ui, selected_particle_id: &mut Option<u32>) {
+        let limits = self.parameters.limits;
+        let domain_size = limits.size();
+        let length_suffix = self.length_unit.suffix();
+
+        ui.horizontal(|ui| {
+            ui.vertical(|ui| {
+                ui.heading("Symulacja");
+
+                ui.horizontal(|ui| {
+                    ui.label("Wymiary");
+                    let plane = ui.radio_value(&mut self.mode, Mode::Plane, "2D");
+                    let space = ui.radio_value(&mut self.mode, Mode::Space, "3D");
+                    /* Każdy tryb ma swoje cząsteczki, więc śledzona i tak by zniknęła,
+                     * a czas, liczniki i wykresy dotyczyłyby poprzedniego trybu. */
+                    if plane.changed() || space.changed() {
+                        self.tracked_particle = None;
+                        self.bond_start = None;
+                        self.reset_diagnostics();
+
+                        if self.mode == Mode::Space && self.click_action == ClickAction::Obstacle {
+                            self.click_action = ClickAction::Add;
+                        }
+                    }
+                });
+
+                ui.label("Szykość symulacji");
+                /* Fixed decimals, bo inaczej całe UI się przesuwa gdy zmienia się
+                 * liczba cyfr po przecinku. */
+                ui.add(egui::Slider::new(&mut self.time_multiplier, 0.0..=1.0).fixed_decimals(2));
+
+                ui.label("Metoda całkowania");
+                egui::ComboBox::from_id_source("integrator")
+                    .selected_text(self.integrator.name())
+                    .show_ui(ui, |ui| {
+                        for integrator in Integrator::ALL {
+                            ui.selectable_value(&mut self.integrator, integrator, integrator.name());
+                        }
+                    });
+
+                /* Verlet i leapfrog liczą siłę Lorentza ze starej prędkości i orbity się rozkręcają. */
+                if !self.parameters.magnetic_field.is_zero()
+                    && !matches!(self.integrator, Integrator::Boris | Integrator::RungeKutta4) {
+                    ui.colored_label(Color32::YELLOW, "W polu magnetycznym lepiej użyć metody Borisa");
+                }
+
+                ui.checkbox(&mut self.adaptive_timestep.enabled, "Adaptacyjny krok czasowy");
+                ui.add_enabled(
+                    self.adaptive_timestep.enabled,
+                    egui::Slider::new(&mut self.adaptive_timestep.tolerance, 0.000001..=0.01)
+                        .logarithmic(true)
+                        .text("Tolerancja")
+                        .custom_formatter(|value, _| format!("{:.0e}", value))
+                );
+                ui.add_enabled(
+                    self.adaptive_timestep.enabled,
+                    egui::Slider::new(&mut self.adaptive_timestep.max_substeps, 1..=1000)
+                        .logarithmic(true)
+                        .text("Maks. podkroków")
+                );
+                ui.label(format!("Podkroki: {}", self.substeps));
+                ui.label(format!("Czas: {:.2}", self.time));
+
+                ui.label("Liczenie sił");
+                ui.horizontal(|ui| {
+                    for backend in ForceBackend::ALL {
+                        ui.radio_value(&mut self.force_backend, backend, backend.name());
+                    }
+                });
+                ui.add_enabled(
+                    self.force_backend == ForceBackend::BarnesHut,
+                    egui::Slider::new(&mut self.opening_angle, 0.0..=1.5).text("θ").fixed_decimals(2)
+                );
+
+                ui.collapsing("Termostat", |ui| {
+                    let thermostat = &mut self.thermostat;
+
+                    egui::ComboBox::from_id_source("thermostat")
+                        .selected_text(thermostat.kind.name())
+                        .show_ui(ui, |ui| {
+                            for kind in ThermostatKind::ALL {
+                                ui.selectable_value(&mut thermostat.kind, kind, kind.name());
+                            }
+                        });
+                    ui.add_enabled_ui(thermostat.kind != ThermostatKind::None, |ui| {
+                        ui.add(egui::Slider::new(&mut thermostat.temperature, 0.0..=10.0).text("Temperatura").fixed_decimals(2));
+                        ui.add(egui::Slider::new(&mut thermostat.relaxation_time, 0.01..=10.0).logarithmic(true).text("Czas relaksacji"));
+                    });
+                    if matches!(thermostat.kind, ThermostatKind::Langevin | ThermostatKind::Andersen) {
+                        ui.horizontal(|ui| {
+                            ui.label("Ziarno");
+                            if ui.add(egui::DragValue::new(&mut thermostat.seed)).changed() {
+                                thermostat.reseed();
+                            }
+                        });
+                    }
+                });
+                let temperature = match self.mode {
+                    Mode::Plane => thermostat::kinetic_temperature(&self.plane.particles),
+                    Mode::Space => thermostat::kinetic_temperature(&self.space.particles),
+                };
+                ui.label(format!("Temperatura: {:.3}", temperature));
+
+                if ui.button("Nowa symulacja").clicked() {
+                    *selected_particle_id = None;
+                    self.tracked_particle = None;
+                    self.bond_start = None;
+                    self.plane.clear();
+                    self.space.clear();
+                    self.reset_diagnostics();
+                    self.thermostat.reseed();
+                }
+            });
+
+            ui.vertical(|ui| {
+                ui.heading("Interakcje");
+
+                ui.horizontal(|ui| {
+                    if ui.button("Wstawianie").clicked() { self.click_action = ClickAction::Add; }
+                    if ui.button("Śledzenie").clicked() { self.click_action = ClickAction::Track; }
+                    if ui.button("Wiązanie").clicked() {
+                        self.click_action = ClickAction::Bond;
+                        self.bond_start = None;
+                    }
+                    /* Przeszkody rysuje się na wykresie 2D. */
+                    if ui.add_enabled(self.mode == Mode::Plane, egui::Button::new("Przeszkoda")).clicked() {
+                        self.click_action = ClickAction::Obstacle;
+                        self.obstacle_draft.clear();
                     }
+                    if ui.button("Przypinanie").clicked() { self.click_action = ClickAction::Pin; }
+                });
+
+                if self.click_action == ClickAction::Pin {
+                    ui.label("Kliknij cząsteczkę, żeby ją przypiąć albo odpiąć");
+                }
 
-                    /* Opcje */
+                if self.click_action == ClickAction::Obstacle {
+                    ui.label("Klikaj kolejne wierzchołki");
                     ui.horizontal(|ui| {
-                        ui.vertical(|ui| {
-                            ui.heading("Symulacja");
+                        if ui.add_enabled(self.obstacle_draft.len() >= 2, egui::Button::new("Łamana")).clicked() {
+                            let points = std::mem::take(&mut self.obstacle_draft);
+                            self.parameters.obstacles.push(Obstacle::polyline(points));
+                        }
+                        if ui.add_enabled(self.obstacle_draft.len() >= 3, egui::Button::new("Wielokąt")).clicked() {
+                            let points = std::mem::take(&mut self.obstacle_draft);
+                            self.parameters.obstacles.push(Obstacle::polygon(points));
+                        }
+                        if ui.button("Anuluj").clicked() {
+                            self.obstacle_draft.clear();
+                        }
+                    });
+                }
+
+                if self.click_action == ClickAction::Bond {
+                    ui.label(if self.bond_start.is_some() { "Kliknij drugą cząsteczkę" } else { "Kliknij pierwszą cząsteczkę" });
+                    ui.checkbox(&mut self.user_bond_input_state.rigid, "Sztywne");
+                    ui.add_enabled(
+                        !self.user_bond_input_state.rigid,
+                        egui::Slider::new(&mut self.user_bond_input_state.stiffness, 1.0..=2000.0).logarithmic(true).text("Sztywność")
+                    );
+                    ui.add_enabled(
+                        !self.user_bond_input_state.rigid,
+                        egui::Slider::new(&mut self.user_bond_input_state.damping, 0.0..=10.0).text("Tłumienie")
+                    );
+                    if ui.button("Usuń wiązania").clicked() {
+                        match self.mode {
+                            Mode::Plane => self.plane.bonds.clear(),
+                            Mode::Space => self.space.bonds.clear(),
+                        }
+                    }
+                }
+
+                ui.add(egui::Slider::new(&mut self.user_particle_input_state.count, 1..=10).text("Ilość").clamp_to_range(false));
+
+                ui.add(egui::Slider::from_get_set(
+                    std::ops::RangeInclusive::new(-1.0, 1.0),
+                    |x| { 
+                        if let Some(x) = x { 
+                            self.user_particle_input_state.charge = x as Float; 
+                        } 
+                        self.user_particle_input_state.charge as f64 
+                    }
+                ).text("Ładunek").clamp_to_range(true)
+                    .custom_formatter(|value, _| format!("{:+.2}", value))
+                );
+
+                ui.add(egui::Slider::from_get_set(
+                    std::ops::RangeInclusive::new(0.01, 1.0),
+                    |x| { 
+                        if let Some(x) = x { 
+                            self.user_particle_input_state.mass = x as Float; 
+                        } 
+                        self.user_particle_input_state.mass as f64 
+                    }
+                ).text("Masa").clamp_to_range(true).fixed_decimals(2));
+
+                /* Zakres promienia rośnie i maleje razem z obszarem. */
+                let extent = match self.mode {
+                    Mode::Plane => self.parameters.limits.extent(),
+                    Mode::Space => self.cube.size,
+                };
+                ui.add(egui::Slider::new(&mut self.user_particle_input_state.radius, 0.002 * extent..=0.05 * extent)
+                    .text("Promień")
+                    .min_decimals(3)
+                    .suffix(&length_suffix)
+                );
+
+                ui.checkbox(&mut self.user_particle_input_state.chain, "Łańcuch");
+                ui.add_enabled(
+                    self.user_particle_input_state.chain,
+                    egui::Checkbox::new(&mut self.user_particle_input_state.alternate_charges, "Naprzemienne ładunki")
+                );
+                ui.checkbox(&mut self.user_particle_input_state.fixed, "Nieruchome");
+
+                /* W 3D klikając na rzut wybieramy tylko dwie współrzędne, a tak można
+                 * łatwo wypełnić cały sześcian. */
+                if self.mode == Mode::Space && ui.button("Wstaw losowo w sześcianie").clicked() {
+                    let coordinate = Uniform::new_inclusive(self.cube.min, self.cube.max());
+                    for _ in 0..self.user_particle_input_state.count {
+                        let position = Vect3::from_fn(|_, _| self.rng.sample(coordinate));
+                        self.space.add_user_particles(
+                            &self.cube,
+                            position,
+                            UserParticleInputState { count: 1, ..self.user_particle_input_state },
+                            &self.user_bond_input_state,
+                            &mut self.next_particle_id,
+                            &mut self.rng,
+                        );
+                    }
+                }
+
+            });
+
+            ui.vertical(|ui| {
+                ui.heading("Parametry");
+
+                /* Zestawy ustawiają tylko część parametrów, więc nie da się ich rozpoznać po samych
+                 * wartościach. Pokazujemy ostatnio wybrany, dopóki niczego nie zmieniono. */
+                let presets = SimulationParameters::presets();
+                let selected = self.preset
+                    .as_ref()
+                    .filter(|(_, applied)| *applied == self.parameters)
+                    .map(|(index, _)| *index);
+
+                egui::ComboBox::from_id_source("parameters_preset")
+                    .selected_text(selected.map_or("Własne", |index| presets[index].name))
+                    .show_ui(ui, |ui| {
+                        for (index, preset) in presets.iter().enumerate() {
+                            if ui.selectable_label(selected == Some(index), preset.name).clicked() {
+                                preset.apply(&mut self.parameters);
+                                self.preset = Some((index, self.parameters.clone()));
+                            }
+                        }
+                    });
+
+                ui.add(egui::Slider::new(&mut self.parameters.electro_k, 0.0..=5.0).text("Stała elektryczna").fixed_decimals(2));
+
+                ui.label("Grawitacja");
+                egui::ComboBox::from_id_source("gravity_mode")
+                    .selected_text(self.parameters.gravity_mode.name())
+                    .show_ui(ui, |ui| {
+                        for mode in GravityMode::ALL {
+                            ui.selectable_value(&mut self.parameters.gravity_mode, mode, mode.name());
+                        }
+                    });
+                ui.add_enabled(
+                    self.parameters.gravity_mode.uniform(),
+                    egui::Slider::new(&mut self.parameters.gravity_k, 0.0..=20.0).text("Jednorodna").fixed_decimals(2)
+                );
+                ui.add_enabled(
+                    self.parameters.gravity_mode.mutual(),
+                    egui::Slider::new(&mut self.parameters.mutual_gravity_k, 0.0..=10.0).text("Wzajemna").fixed_decimals(2)
+                );
+
+                ui.label("Opór");
+                egui::ComboBox::from_id_source("drag_model")
+                    .selected_text(self.parameters.drag_model.name())
+                    .show_ui(ui, |ui| {
+                        for model in DragModel::ALL {
+                            ui.selectable_value(&mut self.parameters.drag_model, model, model.name());
+                        }
+                    });
+                ui.add_enabled(
+                    self.parameters.drag_model.linear(),
+                    egui::Slider::new(&mut self.parameters.linear_drag_k, 0.0..=5.0).text("Liniowy").fixed_decimals(2)
+                );
+                ui.add_enabled(
+                    self.parameters.drag_model.quadratic(),
+                    egui::Slider::new(&mut self.parameters.drag_k, 0.0..=2.0).text("Kwadratowy").fixed_decimals(2)
+                );
+                ui.add_enabled_ui(self.parameters.drag_model != DragModel::None, |ui| {
+                    ui.add(egui::Slider::new(&mut self.parameters.fluid_velocity.x, -5.0..=5.0).text("Płyn vx").fixed_decimals(2));
+                    ui.add(egui::Slider::new(&mut self.parameters.fluid_velocity.y, -5.0..=5.0).text("Płyn vy").fixed_decimals(2));
+                });
+
+                ui.label("Zmiękczenie");
+                egui::ComboBox::from_id_source("softening")
+                    .selected_text(self.parameters.softening.name())
+                    .show_ui(ui, |ui| {
+                        for softening in Softening::ALL {
+                            ui.selectable_value(&mut self.parameters.softening, softening, softening.name());
+                        }
+                    });
 
-                            ui.label("Szykość symulacji");
-                            /* Fixed decimals, bo inaczej całe UI się przesuwa gdy zmienia się
-                             * liczba cyfr po przecinku. */
-                            ui.add(egui::Slider::new(&mut self.time_multiplier, 0.0..=1.0).fixed_decimals(2));
+                if self.parameters.softening == Softening::HardCutoff {
+                    ui.add(egui::Slider::new(&mut self.parameters.eps, 0.000001..=0.01)
+                        .logarithmic(true)
+                        .text("EPS")
+                        .custom_formatter(|value, _| format!("{:.0e}", value))
+                    );
+                } else {
+                    ui.add(egui::Slider::new(&mut self.parameters.softening_length, 0.0001..=0.1)
+                        .logarithmic(true)
+                        .text("Długość h")
+                    );
+                }
 
-                            if ui.button("Nowa symulacja").clicked() {
-                                selected_particle_id = None;
-                                self.tracked_particle = None;
-                                self.particles.clear();
+                ui.label("Odpychanie krótkozasięgowe");
+                egui::ComboBox::from_id_source("pair_potential")
+                    .selected_text(self.parameters.pair_potential.name())
+                    .show_ui(ui, |ui| {
+                        for potential in PairPotential::ALL {
+                            ui.selectable_value(&mut self.parameters.pair_potential, potential, potential.name());
+                        }
+                    });
+                if self.parameters.pair_potential != PairPotential::None {
+                    ui.add(egui::Slider::new(&mut self.parameters.lj_sigma, 0.005..=0.1).text("σ").fixed_decimals(3));
+                    ui.add(egui::Slider::new(&mut self.parameters.lj_epsilon, 0.0..=5.0).text("ε").fixed_decimals(2));
+                }
+
+                ui.label("Zderzenia");
+                egui::ComboBox::from_id_source("collision_mode")
+                    .selected_text(self.parameters.collision_mode.name())
+                    .show_ui(ui, |ui| {
+                        for mode in CollisionMode::ALL {
+                            ui.selectable_value(&mut self.parameters.collision_mode, mode, mode.name());
+                        }
+                    });
+                ui.add_enabled(
+                    self.parameters.collision_mode != CollisionMode::None,
+                    egui::Slider::new(&mut self.parameters.restitution, 0.0..=1.0).text("Restytucja").fixed_decimals(2)
+                );
+
+                if self.parameters.collision_mode == CollisionMode::Merge {
+                    ui.label("Połączone spoza zakresu");
+                    egui::ComboBox::from_id_source("merge_policy")
+                        .selected_text(self.parameters.merge_policy.name())
+                        .show_ui(ui, |ui| {
+                            for policy in MergePolicy::ALL {
+                                ui.selectable_value(&mut self.parameters.merge_policy, policy, policy.name());
                             }
                         });
+                }
 
-                        ui.vertical(|ui| {
-                            ui.heading("Interakcje");
+                /* Ściany i wymiary obszaru 2D nic nie zmieniają w sześcianie, który ma swoje
+                 * ustawienia w sekcji "Sześcian". */
+                if self.mode == Mode::Plane {
+                    ui.collapsing("Ściany", |ui| {
+                        let pressures = self.pressure_gauge.averages().map(|averages| averages.walls);
 
+                        for (i, wall) in self.parameters.limits.walls.iter_mut().enumerate() {
                             ui.horizontal(|ui| {
-                                if ui.button("Wstawianie").clicked() { self.click_action = ClickAction::Add; }
-                                if ui.button("Śledzenie").clicked() { self.click_action = ClickAction::Track; }
+                                ui.label(Limits::WALL_NAMES[i]);
+                                egui::ComboBox::from_id_source(("wall", i))
+                                    .selected_text(wall.behaviour.name())
+                                    .show_ui(ui, |ui| {
+                                        for behaviour in WallBehaviour::ALL {
+                                            ui.selectable_value(&mut wall.behaviour, behaviour, behaviour.name());
+                                        }
+                                    });
                             });
 
-                            ui.add(egui::Slider::new(&mut self.user_particle_input_state.count, 1..=10).text("Ilość").clamp_to_range(false));
-
-                            ui.add(egui::Slider::from_get_set(
-                                std::ops::RangeInclusive::new(-1.0, 1.0),
-                                |x| { 
-                                    if let Some(x) = x { 
-                                        self.user_particle_input_state.charge = x as f32; 
-                                    } 
-                                    self.user_particle_input_state.charge as f64 
+                            match wall.behaviour {
+                                WallBehaviour::Reflect => {
+                                    ui.add(egui::Slider::new(&mut wall.restitution, 0.0..=1.0).text("Restytucja").fixed_decimals(2));
                                 }
-                            ).text("Ładunek").clamp_to_range(true)
-                                .custom_formatter(|value, _| format!("{:+.2}", value))
-                            );
-
-                            ui.add(egui::Slider::from_get_set(
-                                std::ops::RangeInclusive::new(0.01, 1.0),
-                                |x| { 
-                                    if let Some(x) = x { 
-                                        self.user_particle_input_state.mass = x as f32; 
-                                    } 
-                                    self.user_particle_input_state.mass as f64 
+                                WallBehaviour::Absorb => {
+                                    ui.label(format!("Pochłonięte: {}", self.plane.absorbed[i]));
+                                }
+                                WallBehaviour::Wrap => {}
+                                WallBehaviour::Thermal => {
+                                    ui.add(egui::Slider::new(&mut wall.temperature, 0.0..=10.0).text("Temperatura").fixed_decimals(2));
                                 }
-                            ).text("Masa").clamp_to_range(true).fixed_decimals(2));
+                            }
 
+                            if let Some(Some(pressure)) = pressures.as_ref().map(|pressures| pressures[i]) {
+                                ui.label(format!("Ciśnienie: {:.3}", pressure));
+                            }
+                        }
+                    });
+                }
+
+                let (absorbed, active_forces) = match self.mode {
+                    Mode::Plane => (self.plane.absorbed.iter().sum::<u32>(), self.plane.interactions.active_names(&self.parameters)),
+                    Mode::Space => (self.space.absorbed.iter().sum::<u32>(), self.space.interactions.active_names(&self.parameters)),
+                };
+                ui.label(format!("Pochłonięte cząsteczki: {}", absorbed));
+                ui.label(format!("Aktywne siły: {}", active_forces.join(", ")));
+
+                if self.mode == Mode::Space {
+                    ui.collapsing("Sześcian", |ui| {
+                        ui.add(egui::Slider::new(&mut self.cube.size, Limits::SIZE_RANGE)
+                            .logarithmic(true)
+                            .text("Bok")
+                            .suffix(&length_suffix)
+                        );
+
+                        let wall = &mut self.cube.wall;
+                        ui.horizontal(|ui| {
+                            ui.label("Ściany");
+                            egui::ComboBox::from_id_source("cube_wall")
+                                .selected_text(wall.behaviour.name())
+                                .show_ui(ui, |ui| {
+                                    for behaviour in WallBehaviour::ALL {
+                                        ui.selectable_value(&mut wall.behaviour, behaviour, behaviour.name());
+                                    }
+                                });
                         });
 
-                        ui.vertical(|ui| {
-                            ui.heading("Wyświetlanie");
+                        match wall.behaviour {
+                            WallBehaviour::Reflect => {
+                                ui.add(egui::Slider::new(&mut wall.restitution, 0.0..=1.0).text("Restytucja").fixed_decimals(2));
+                            }
+                            WallBehaviour::Thermal => {
+                                ui.add(egui::Slider::new(&mut wall.temperature, 0.0..=10.0).text("Temperatura").fixed_decimals(2));
+                            }
+                            WallBehaviour::Absorb | WallBehaviour::Wrap => {}
+                        }
 
+                        /* Wszystkie ściany zachowują się tak samo, ale liczniki i ciśnienie są osobno. */
+                        let pressures = self.pressure_gauge.averages().map(|averages| averages.walls);
+                        for (i, name) in Cube::WALL_NAMES.iter().enumerate() {
+                            match (wall.behaviour, pressures.as_ref().and_then(|pressures| pressures.get(i).copied().flatten())) {
+                                (WallBehaviour::Absorb, _) => {
+                                    ui.label(format!("{}: pochłonięte {}", name, self.space.absorbed[i]));
+                                }
+                                (_, Some(pressure)) => {
+                                    ui.label(format!("{}: ciśnienie {:.3}", name, pressure));
+                                }
+                                (_, None) => {}
+                            }
+                        }
+                    });
+                }
 
-                            ui.label("Motyw");
-                            egui::widgets::global_dark_light_mode_buttons(ui);
+                ui.collapsing("Obszar", |ui| {
+                    if self.mode == Mode::Plane {
+                        let mut size = domain_size;
+                        let width = ui.add(egui::Slider::new(&mut size.x, Limits::SIZE_RANGE)
+                            .logarithmic(true)
+                            .text("Szerokość")
+                            .suffix(&length_suffix)
+                        );
+                        let height = ui.add(egui::Slider::new(&mut size.y, Limits::SIZE_RANGE)
+                            .logarithmic(true)
+                            .text("Wysokość")
+                            .suffix(&length_suffix)
+                        );
+                        /* Cząsteczki, które są teraz poza obszarem, w następnym kroku obsłużą ściany. */
+                        if width.changed() || height.changed() {
+                            self.parameters.limits.set_size(size.x, size.y);
+                        }
+                    }
 
+                    ui.label("Jednostka długości");
+                    egui::ComboBox::from_id_source("length_unit")
+                        .selected_text(self.length_unit.name())
+                        .show_ui(ui, |ui| {
+                            for unit in LengthUnit::ALL {
+                                ui.selectable_value(&mut self.length_unit, unit, unit.name());
+                            }
                         });
-                    });
                 });
 
-                ui.vertical(|ui| {
-                    {
-                        /* Prawdziwy histogram prędkości. */
-                        let mut bars: Vec<Bar> = Vec::new();
-                        let values: Vec<f32> = self
-                            .particles
-                            .iter()
-                            .map(|p| (p.velocity[0].powi(2) + p.velocity[1].powi(2)).sqrt())
-                            .map(|v| (v / self.velocity_precision).floor() * self.velocity_precision)
-                            .collect();
-                        for v in values {
-                            let bar = Bar::new((v + self.velocity_precision / 2.0) as f64, 1.0);
-                            let index = bars.iter().position(|b| b.argument == bar.argument);
-                            match index {
-                                None => bars.push(bar),
-                                Some(i) => bars.get_mut(i).unwrap().value += 1.0,
+                ui.collapsing("Przeszkody", |ui| {
+                    let mut removed_obstacle = None;
+                    for (i, obstacle) in self.parameters.obstacles.iter_mut().enumerate() {
+                        ui.horizontal(|ui| {
+                            ui.label(format!(
+                                "{} {} ({} wierzch.)",
+                                if obstacle.closed { "Wielokąt" } else { "Łamana" },
+                                i + 1,
+                                obstacle.points.len()
+                            ));
+                            if ui.small_button("Usuń").clicked() {
+                                removed_obstacle = Some(i);
                             }
-                        }
-                            
+                        });
+                        ui.add(egui::Slider::new(&mut obstacle.restitution, 0.0..=1.0).text("Restytucja").fixed_decimals(2));
+                    }
 
-                        ui.heading("Histogram prędkości");
-
-                        let chart = BarChart::new(bars)
-                            .width(self.velocity_precision as f64)
-                            .color(Color32::LIGHT_BLUE);
-                        Plot::new("predkosc")
-                            .width(plot_size)
-                            .height(plot_size)
-                            .allow_drag(false)
-                            .allow_scroll(false)
-                            .allow_zoom(false)
-                            .allow_boxed_zoom(false)
-                            .include_x(0)
-                            .include_x(8.0)
-                            .include_y(0)
-                            .include_y(4.0)
-                            .auto_bounds_x()
-                            .auto_bounds_y()
-                            .show(ui, |plot_ui| plot_ui.bar_chart(chart));
-
-                        ui.label("Precyzja histogramu prędkości");
-                        ui.add(egui::Slider::new(&mut self.velocity_precision, 0.2..=2.0));
+                    if let Some(i) = removed_obstacle {
+                        self.parameters.obstacles.remove(i);
                     }
 
-                    ui.add_space(16.0);
-
-                    {
-                        /* Histogram energii. */
-                        let mut bars: Vec<Bar> = Vec::new();
-                        let values: Vec<f32> = self
-                            .particles
-                            .iter()
-                            .map(|p| {
-                                let velocity = (p.velocity[0].powi(2) + p.velocity[1].powi(2)).sqrt();
-                                let mass = p.mass;
-                                mass * velocity.powi(2) * 0.5
-                            })
-                            .map(|v| (v / self.energy_precision).floor() * self.energy_precision)
-                            .collect();
-                        for v in values {
-                            let bar = Bar::new((v + self.energy_precision / 2.0) as f64, 1.0);
-                            let index = bars.iter().position(|b| b.argument == bar.argument);
-                            match index {
-                                None => bars.push(bar),
-                                Some(i) => bars.get_mut(i).unwrap().value += 1.0,
+                    if self.parameters.obstacles.is_empty() {
+                        ui.label("Przeszkody rysuje się w trybie \"Przeszkoda\"");
+                    } else if self.mode == Mode::Space {
+                        ui.label("W 3D przeszkody z obszaru 2D są przeniesione na ścianę xy sześcianu");
+                    }
+                });
+
+                ui.collapsing("Zewnętrzne pole elektryczne", |ui| {
+                    let electric_field = &mut self.parameters.electric_field;
+
+                    egui::ComboBox::from_id_source("waveform")
+                        .selected_text(electric_field.waveform.name())
+                        .show_ui(ui, |ui| {
+                            for waveform in Waveform::ALL {
+                                ui.selectable_value(&mut electric_field.waveform, waveform, waveform.name());
                             }
-                        }
+                        });
+                    ui.add(egui::Slider::new(&mut electric_field.amplitude.x, -20.0..=20.0).text("Ex").fixed_decimals(1));
+                    ui.add(egui::Slider::new(&mut electric_field.amplitude.y, -20.0..=20.0).text("Ey").fixed_decimals(1));
+                    ui.add_enabled(
+                        electric_field.waveform != Waveform::Constant,
+                        egui::Slider::new(&mut electric_field.frequency, 0.01..=20.0).logarithmic(true).text("Częstotliwość")
+                    );
+                    if electric_field.waveform == Waveform::Pulses {
+                        ui.add(egui::Slider::new(&mut electric_field.duty_cycle, 0.0..=1.0).text("Wypełnienie").fixed_decimals(2));
+                    }
+
+                    let current = electric_field.at(self.time);
+                    ui.label(format!("Teraz: ({:.2}, {:.2})", current.x, current.y));
+                });
+
+                ui.collapsing("Pole magnetyczne", |ui| {
+                    let magnetic_field = &mut self.parameters.magnetic_field;
 
-                        ui.heading("Histogram energii");
-
-                        let chart = BarChart::new(bars)
-                            .width(self.energy_precision as f64)
-                            .color(Color32::LIGHT_GREEN);
-                        Plot::new("energia")
-                            .width(plot_size)
-                            .height(plot_size)
-                            .allow_drag(false)
-                            .allow_scroll(false)
-                            .allow_zoom(false)
-                            .allow_boxed_zoom(false)
-                            .include_x(0)
-                            .include_x(20.0)
-                            .include_y(0)
-                            .include_y(4.0)
-                            .auto_bounds_x()
-                            .auto_bounds_y()
-                            .show(ui, |plot_ui| plot_ui.bar_chart(chart));
-
-                        ui.label("Precyzja histogramu energii");
-                        ui.add(egui::Slider::new(&mut self.energy_precision, 0.4..=4.0));
+                    ui.add(egui::Slider::new(&mut magnetic_field.uniform, -30.0..=30.0).text("Bz").fixed_decimals(1));
+
+                    let mut removed_region = None;
+                    for (i, region) in magnetic_field.regions.iter_mut().enumerate() {
+                        ui.horizontal(|ui| {
+                            ui.label(format!("Obszar {}", i + 1));
+                            if ui.small_button("Usuń").clicked() {
+                                removed_region = Some(i);
+                            }
+                        });
+                        ui.add(egui::Slider::new(&mut region.bz, -30.0..=30.0).text("Bz").fixed_decimals(1));
+                        ui.add(egui::Slider::new(&mut region.min.x, limits.min.x..=limits.max.x).text("x min").fixed_decimals(2).suffix(&length_suffix));
+                        ui.add(egui::Slider::new(&mut region.max.x, limits.min.x..=limits.max.x).text("x max").fixed_decimals(2).suffix(&length_suffix));
+                        ui.add(egui::Slider::new(&mut region.min.y, limits.min.y..=limits.max.y).text("y min").fixed_decimals(2).suffix(&length_suffix));
+                        ui.add(egui::Slider::new(&mut region.max.y, limits.min.y..=limits.max.y).text("y max").fixed_decimals(2).suffix(&length_suffix));
                     }
 
+                    if let Some(i) = removed_region {
+                        magnetic_field.regions.remove(i);
+                    }
+
+                    if ui.button("Dodaj obszar").clicked() {
+                        magnetic_field.regions.push(MagneticRegion {
+                            min: limits.min + domain_size * 0.4,
+                            max: limits.min + domain_size * 0.6,
+                            bz: 10.0,
+                        });
+                    }
                 });
+            });
 
-                ui.vertical(|ui| {
-                    {
-                        /* Pole wektorowe siły elektrostatycznej. */
-
-                        let vector_field = Plot::new("vector_field")
-                            .view_aspect(1.0)
-                            .width(plot_size)
-                            .height(plot_size)
-                            .allow_drag(false)
-                            .allow_scroll(false)
-                            .allow_zoom(false)
-                            .allow_boxed_zoom(false)
-                            .include_x(0.0)
-                            .include_x(1.0)
-                            .include_y(0.0)
-                            .include_y(1.0)
-                            .show_axes([false, false]);
-
-                        ui.heading("Pole elektryczne");
-
-                        /* Ile wektorów chcemy mieć w każdym wymiarze. */
-                        let resolution = 8;
-                        let arrow_length = 0.1;
-
-                        let mut arrow_origins = Vec::with_capacity(resolution);
-                        let mut arrow_tips = Vec::with_capacity(resolution);
-
-                        for x in 0..resolution {
-                            for y in 0..resolution {
-                                let [x, y] = [(x as f64 + 0.5)/resolution as f64, (y as f64 + 0.5)/resolution as f64];
-                                let mut force = Particle::new(u32::MAX, x as f32, y as f32, 1.0, 0.5)
-                                    .net_electrostatic_force(self.particles.iter());
-
-                                if force.magnitude() != 0.0 {
-                                    force = force.normalize() * (force.magnitude().atan() / (std::f32::consts::PI / 2.0)).powf(4.0) * arrow_length;
+            ui.vertical(|ui| {
+                ui.heading("Wyświetlanie");
+
+
+                ui.label("Motyw");
+                egui::widgets::global_dark_light_mode_buttons(ui);
+
+            });
+        });
+    }
+
+    /* Energia w czasie: składowe, całkowita i jej względny dryf. */
+    fn energy_panel(&self, ui: &mut egui::Ui, plot_size: f32) {
+        let history = &self.energy_history;
+
+        ui.heading("Energia");
+
+        Plot::new("energy")
+            .width(plot_size * 1.5)
+            .height(plot_size)
+            .allow_drag(false)
+            .allow_scroll(false)
+            .allow_zoom(false)
+            .allow_boxed_zoom(false)
+            .legend(egui::widgets::plot::Legend::default().position(egui::widgets::plot::Corner::LeftTop))
+            .show(ui, |plot_ui| {
+                for (name, points) in &history.components {
+                    plot_ui.line(
+                        egui::widgets::plot::Line::new(egui::widgets::plot::PlotPoints::from_iter(points.iter().copied()))
+                            .name(name)
+                    );
+                }
+                plot_ui.line(
+                    egui::widgets::plot::Line::new(egui::widgets::plot::PlotPoints::from_iter(history.total.iter().copied()))
+                        .name("Całkowita")
+                        .color(Color32::WHITE)
+                        .width(2.0)
+                );
+            });
+
+        ui.heading("Dryf energii całkowitej");
+
+        Plot::new("energy_drift")
+            .width(plot_size * 1.5)
+            .height(plot_size)
+            .allow_drag(false)
+            .allow_scroll(false)
+            .allow_zoom(false)
+            .allow_boxed_zoom(false)
+            .show(ui, |plot_ui| {
+                plot_ui.line(
+                    egui::widgets::plot::Line::new(egui::widgets::plot::PlotPoints::from_iter(history.drift.iter().copied()))
+                        .color(Color32::LIGHT_RED)
+                );
+            });
+
+        if let (Some(total), Some(drift)) = (history.total.back(), history.drift.back()) {
+            ui.label(format!("Całkowita: {:.4}, dryf: {:+.2e}", total[1], drift[1]));
+        }
+        ui.label("Dryf jest liczony od ostatniej zmiany liczby cząsteczek lub oddziaływań");
+    }
+
+    /* Pęd i moment pędu w czasie oraz skąd pęd przychodzi. */
+    fn momentum_panel(&self, ui: &mut egui::Ui, plot_size: f32) {
+        let history = &self.momentum_history;
+        /* W 2D pęd ma tylko składowe x, y, a moment pędu tylko z. */
+        let (linear_axes, angular_axes) = match self.mode {
+            Mode::Plane => (&[0, 1][..], &[2][..]),
+            Mode::Space => (&[0, 1, 2][..], &[0, 1, 2][..]),
+        };
+        let axis_names = ["x", "y", "z"];
+        let momentum_plot = |id: &str| {
+            Plot::new(id)
+                .width(plot_size * 1.5)
+                .height(plot_size * 0.75)
+                .allow_drag(false)
+                .allow_scroll(false)
+                .allow_zoom(false)
+                .allow_boxed_zoom(false)
+                .legend(egui::widgets::plot::Legend::default().position(egui::widgets::plot::Corner::LeftTop))
+        };
+
+        ui.heading("Pęd całkowity");
+
+        momentum_plot("linear_momentum").show(ui, |plot_ui| {
+            for &axis in linear_axes {
+                plot_ui.line(
+                    egui::widgets::plot::Line::new(egui::widgets::plot::PlotPoints::from_iter(history.linear[axis].iter().copied()))
+                        .name(format!("p{}", axis_names[axis]))
+                );
+            }
+        });
+
+        ui.heading("Moment pędu względem środka masy");
+
+        momentum_plot("angular_momentum").show(ui, |plot_ui| {
+            for &axis in angular_axes {
+                plot_ui.line(
+                    egui::widgets::plot::Line::new(egui::widgets::plot::PlotPoints::from_iter(history.angular[axis].iter().copied()))
+                        .name(format!("L{}", axis_names[axis]))
+                );
+            }
+        });
+
+        ui.heading("Przekazany pęd");
+
+        momentum_plot("impulses").show(ui, |plot_ui| {
+            for (name, points, color) in [
+                ("Ściany", &history.walls, Color32::LIGHT_BLUE),
+                ("Opór", &history.drag, Color32::LIGHT_GREEN),
+                ("Pola zewnętrzne", &history.external, Color32::LIGHT_YELLOW),
+                ("Więzy i przypięte", &history.constraints, Color32::GRAY),
+                ("Między cząsteczkami", &history.exchanged, Color32::GOLD),
+                ("Niezrównoważenie par", &history.imbalance, Color32::LIGHT_RED),
+            ] {
+                plot_ui.line(
+                    egui::widgets::plot::Line::new(egui::widgets::plot::PlotPoints::from_iter(points.iter().copied()))
+                        .name(name)
+                        .color(color)
+                );
+            }
+        });
+
+        let impulses = &self.impulses;
+        let vector = |v: Vect3| match self.mode {
+            Mode::Plane => format!("({:.3}, {:.3})", v.x, v.y),
+            Mode::Space => format!("({:.3}, {:.3}, {:.3})", v.x, v.y, v.z),
+        };
+        ui.label(format!("Od ścian: {}", vector(impulses.walls)));
+        ui.label(format!("Od oporu: {}", vector(impulses.drag)));
+        ui.label(format!("Od pól zewnętrznych, przeszkód i termostatu: {}", vector(impulses.external)));
+        ui.label(format!("Od więzów i przypiętych cząsteczek: {}", vector(impulses.constraints)));
+        let linear = match self.mode {
+            Mode::Plane => momentum::linear_momentum(&self.plane.particles),
+            Mode::Space => momentum::linear_momentum(&self.space.particles),
+        };
+        ui.label(format!("Pęd całkowity: {}, suma popędów: {}", vector(linear), vector(impulses.total())));
+        ui.label("Jeśli cząsteczki wstawiano w spoczynku, obie wartości powinny być równe");
+        ui.label(format!(
+            "Suma sił par: {:.2e} wymienionego pędu ({:.3})",
+            if impulses.exchanged > 0.0 { impulses.imbalance.magnitude() / impulses.exchanged } else { 0.0 },
+            impulses.exchanged,
+        ));
+        ui.label("Z trzeciej zasady dynamiki suma sił par powinna być zerowa");
+    }
+
+    /* Ciśnienie na ścianach i temperatura uśrednione w oknie oraz krzywe P-T i P-N. */
+    fn pressure_panel(&mut self, ui: &mut egui::Ui, plot_size: f32) {
+        let limits = self.parameters.limits;
+
+        ui.heading("Ciśnienie i temperatura");
+
+        ui.add(
+            egui::Slider::new(&mut self.pressure_gauge.window, 0.1..=20.0)
+                .logarithmic(true)
+                .text("Okno uśredniania")
+        );
+
+        let gauge = &self.pressure_gauge;
+        let averages = gauge.averages();
+        /* Pole pudełka w 2D albo objętość sześcianu w 3D, do porównania z gazem doskonałym PV = NT. */
+        let volume = match self.mode {
+            Mode::Plane => limits.size().x * limits.size().y,
+            Mode::Space => self.cube.size.powi(3),
+        };
+        let pressure_plot = |id: &str| {
+            Plot::new(id)
+                .width(plot_size * 1.5)
+                .height(plot_size * 0.75)
+                .allow_drag(false)
+                .allow_scroll(false)
+                .allow_zoom(false)
+                .allow_boxed_zoom(false)
+                .include_x(0.0)
+                .include_y(0.0)
+                .legend(egui::widgets::plot::Legend::default().position(egui::widgets::plot::Corner::LeftTop))
+        };
+        /* Prosta P = slope * x gazu doskonałego przez cały zakres zebranych punktów. */
+        let ideal_gas = |points: &std::collections::VecDeque<[f64; 2]>, slope: f64| {
+            let end = points.iter().map(|point| point[0]).fold(0.0, f64::max);
+            egui::widgets::plot::Line::new(egui::widgets::plot::PlotPoints::from(vec![[0.0, 0.0], [end, slope * end]]))
+                .name("Gaz doskonały")
+                .color(Color32::GRAY)
+        };
+
+        ui.label("W czasie");
+
+        Plot::new("pressure_time")
+            .width(plot_size * 1.5)
+            .height(plot_size * 0.75)
+            .allow_drag(false)
+            .allow_scroll(false)
+            .allow_zoom(false)
+            .allow_boxed_zoom(false)
+            .include_y(0.0)
+            .legend(egui::widgets::plot::Legend::default().position(egui::widgets::plot::Corner::LeftTop))
+            .show(ui, |plot_ui| {
+                plot_ui.line(
+                    egui::widgets::plot::Line::new(egui::widgets::plot::PlotPoints::from_iter(gauge.pressure.iter().copied()))
+                        .name("Ciśnienie")
+                        .color(Color32::LIGHT_BLUE)
+                );
+                plot_ui.line(
+                    egui::widgets::plot::Line::new(egui::widgets::plot::PlotPoints::from_iter(gauge.temperature.iter().copied()))
+                        .name("Temperatura")
+                        .color(Color32::LIGHT_RED)
+                );
+            });
+
+        ui.label("Ciśnienie od temperatury (P-T)");
+
+        pressure_plot("pressure_temperature").show(ui, |plot_ui| {
+            if let Some(ref averages) = averages {
+                plot_ui.line(ideal_gas(&gauge.pressure_temperature, (averages.particle_count / volume) as f64));
+            }
+            plot_ui.points(
+                Points::new(egui::widgets::plot::PlotPoints::from_iter(gauge.pressure_temperature.iter().copied()))
+                    .name("Pomiary")
+                    .radius(2.0)
+                    .color(Color32::LIGHT_BLUE)
+            );
+        });
+
+        ui.label("Ciśnienie od liczby cząsteczek (P-N)");
+
+        pressure_plot("pressure_count").show(ui, |plot_ui| {
+            if let Some(ref averages) = averages {
+                plot_ui.line(ideal_gas(&gauge.pressure_count, (averages.temperature / volume) as f64));
+            }
+            plot_ui.points(
+                Points::new(egui::widgets::plot::PlotPoints::from_iter(gauge.pressure_count.iter().copied()))
+                    .name("Pomiary")
+                    .radius(2.0)
+                    .color(Color32::LIGHT_BLUE)
+            );
+        });
+
+        match averages {
+            Some(averages) => {
+                let pressure = averages.total.map_or("brak ścian z odbiciami".to_string(), |pressure| format!("{:.3}", pressure));
+                ui.label(format!("Ciśnienie: {}, temperatura: {:.3}", pressure, averages.temperature));
+                ui.label(format!(
+                    "Gaz doskonały: {:.3} dla {:.1} cząsteczek",
+                    averages.particle_count * averages.temperature / volume,
+                    averages.particle_count,
+                ));
+            }
+            None => {
+                ui.label("Brak pomiarów");
+            }
+        }
+        ui.label("Punkty krzywych są dopisywane raz na pełne okno");
+    }
+}
+
+impl eframe::App for MyEguiApp {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        /* Potrzebne do obliczania współrzędnych przy dodawaniu cząsteczek myszką:
+         * wartości współrzędnych kursora w układzie współrzędnych wykresu.*/
+        let mut particle_plot_pointer_coordinates = None;
+
+        /* Id cząsteczki aktualnie pod kursorem (może być inna niż aktualnie śledzona). */
+        let mut selected_particle_id: Option<u32> = None;
+        /* Punkt w sześcianie pod kursorem na którymś z rzutów w trybie 3D. */
+        let mut space_pointer_position: Option<Vect3> = None;
+
+        egui::CentralPanel::default().show(ctx, |ui| {
+            /* Wielkość okienka z symulacją (dłuższego boku, krótszy zależy od proporcji obszaru). */
+            let simulation_plot_size = 500.0;
+            /* Wielkość pozostałych wykresów. */
+            let plot_size = 200.0;
+
+            let limits = self.parameters.limits;
+            let domain_size = limits.size();
+            let length_suffix = self.length_unit.suffix();
+
+            /* Zakres małych wykresów obszaru symulacji. W 3D to widok z kamery, dla której środek
+             * sześcianu jest w zerze. */
+            let (plot_min, plot_max) = match self.mode {
+                Mode::Plane => (limits.min.cast::<f64>(), limits.max.cast::<f64>()),
+                Mode::Space => (na::Vector2::repeat(-self.cube.size as f64), na::Vector2::repeat(self.cube.size as f64)),
+            };
+
+            /* Szybkość i masa każdej cząsteczki z aktualnego trybu, do histogramów. */
+            let speeds_and_masses: Vec<(Float, Float)> = match self.mode {
+                Mode::Plane => self.plane.particles.iter().map(|p| (p.velocity.magnitude(), p.mass)).collect(),
+                Mode::Space => self.space.particles.iter().map(|p| (p.velocity.magnitude(), p.mass)).collect(),
+            };
+
+            /* Wszystkie wykresy i opcje nie mieszczą się w mniejszym oknie. */
+            egui::ScrollArea::both().show(ui, |ui| {
+                ui.horizontal(|ui| {
+                    ui.vertical(|ui| {
+                        if self.mode == Mode::Plane {
+                            /* Cząsteczki. */
+                            let aspect = (domain_size.x / domain_size.y) as f32;
+                            let coordinates_suffix = length_suffix.clone();
+                            let markers_plot = Plot::new("markers_demo")
+                                .data_aspect(1.0)
+                                .width(if aspect >= 1.0 { simulation_plot_size } else { simulation_plot_size * aspect.max(0.2) })
+                                .height(if aspect >= 1.0 { simulation_plot_size / aspect.min(5.0) } else { simulation_plot_size })
+                                .allow_drag(false)
+                                .allow_scroll(false)
+                                .allow_zoom(false)
+                                .allow_boxed_zoom(false)
+                                .include_x(limits.min.x)
+                                .include_x(limits.max.x)
+                                .include_y(limits.min.y)
+                                .include_y(limits.max.y)
+                                .coordinates_formatter(
+                                    egui::widgets::plot::Corner::LeftBottom,
+                                    egui::widgets::plot::CoordinatesFormatter::new(move |point, _| {
+                                        format!("x: {:.3}{}\ny: {:.3}{}", point.x, coordinates_suffix, point.y, coordinates_suffix)
+                                    })
+                                );
+
+                            ui.heading("Symulacja");
+
+                            markers_plot.show(ui, |plot_ui| {
+                                /* `pointer_coordinate` zwraca kursor także wtedy, gdy jest poza wykresem
+                                 * (np. nad przyciskiem), a wtedy kliknięcie nie może wstawiać punktów. */
+                                particle_plot_pointer_coordinates = plot_ui.pointer_coordinate().filter(|_| plot_ui.plot_hovered());
+
+                                let pixels_per_unit = pixels_per_unit(plot_ui);
+
+                                /* Szukamy indeksu cząsteczki pod kursorem. */
+                                if let Some(pointer) = particle_plot_pointer_coordinates {
+                                    selected_particle_id = particle_under_pointer(
+                                        self.plane.particles.iter().map(|p| (p.id, [p.position.x as f64, p.position.y as f64])),
+                                        pointer,
+                                        pixels_per_unit,
+                                    );
+                                }
+
+
+                                /* Obszary z innym polem magnetycznym: czerwone dla Bz > 0, niebieskie dla Bz < 0. */
+                                for region in &self.parameters.magnetic_field.regions {
+                                    let color = if region.bz >= 0.0 { Color32::from_rgb(255, 100, 100) } else { Color32::from_rgb(100, 100, 255) };
+                                    plot_ui.polygon(
+                                        egui::widgets::plot::Polygon::new(egui::widgets::plot::PlotPoints::from(vec![
+                                            [region.min.x as f64, region.min.y as f64],
+                                            [region.max.x as f64, region.min.y as f64],
+                                            [region.max.x as f64, region.max.y as f64],
+                                            [region.min.x as f64, region.max.y as f64],
+                                        ]))
+                                        .color(color)
+                                        .fill_alpha(0.1)
+                                    );
                                 }
 
-                                arrow_origins.push([x, y]);
-                                arrow_tips.push([x + force.x as f64, y + force.y as f64]);
+                                draw_obstacles(plot_ui, &self.parameters.obstacles);
+
+                                /* Rysowana przeszkoda, razem z odcinkiem do kursora. */
+                                if self.click_action == ClickAction::Obstacle && !self.obstacle_draft.is_empty() {
+                                    let mut points: Vec<[f64; 2]> = self.obstacle_draft.iter().map(|p| [p.x as f64, p.y as f64]).collect();
+                                    plot_ui.points(Points::new(points.clone()).radius(3.0).color(Color32::YELLOW));
+                                    if let Some(pointer) = particle_plot_pointer_coordinates {
+                                        points.push([pointer.x, pointer.y]);
+                                    }
+                                    plot_ui.line(
+                                        egui::widgets::plot::Line::new(egui::widgets::plot::PlotPoints::from(points))
+                                            .color(Color32::YELLOW)
+                                            .style(egui::widgets::plot::LineStyle::dashed_loose())
+                                    );
+                                }
+
+                                /* Wiązania rysujemy pod cząsteczkami. */
+                                draw_bonds(plot_ui, &self.plane, &self.parameters.limits, |r| Some([r.x as f64, r.y as f64]));
+
+                                for p in &self.plane.particles {
+                                    let color_value = self.particle_color(p.id, p.charge, selected_particle_id);
+
+                                    /* Cząsteczki rysujemy w ich prawdziwym rozmiarze, żeby było widać, kiedy się
+                                     * zderzają, ale nie mniejsze niż 2 piksele, żeby nie zniknęły. */
+                                    let radius = (p.radius as f32 * pixels_per_unit).max(2.0);
+                                    draw_particle(plot_ui, [p.position.x as f64, p.position.y as f64], radius, color_value, p.fixed);
+                                }
+
+                            });
+                        } else {
+                            space_pointer_position = self.space_plots(ui, simulation_plot_size, &mut selected_particle_id);
+                        }
+
+                        /* Opcje */
+                        self.options_panel(ui, &mut selected_particle_id);
+                    });
+
+                    ui.vertical(|ui| {
+                        {
+                            /* Prawdziwy histogram prędkości. */
+                            let mut bars: Vec<Bar> = Vec::new();
+                            let values: Vec<Float> = speeds_and_masses
+                                .iter()
+                                .map(|&(v, _)| (v / self.velocity_precision).floor() * self.velocity_precision)
+                                .collect();
+                            for v in values {
+                                let bar = Bar::new((v + self.velocity_precision / 2.0) as f64, 1.0);
+                                let index = bars.iter().position(|b| b.argument == bar.argument);
+                                match index {
+                                    None => bars.push(bar),
+                                    Some(i) => bars.get_mut(i).unwrap().value += 1.0,
+                                }
                             }
+                            
+
+                            ui.heading("Histogram prędkości");
+
+                            let chart = BarChart::new(bars)
+                                .width(self.velocity_precision as f64)
+                                .color(Color32::LIGHT_BLUE);
+                            Plot::new("predkosc")
+                                .width(plot_size)
+                                .height(plot_size)
+                                .allow_drag(false)
+                                .allow_scroll(false)
+                                .allow_zoom(false)
+                                .allow_boxed_zoom(false)
+                                .include_x(0)
+                                .include_x(8.0)
+                                .include_y(0)
+                                .include_y(4.0)
+                                .auto_bounds_x()
+                                .auto_bounds_y()
+                                .show(ui, |plot_ui| plot_ui.bar_chart(chart));
+
+                            ui.label("Precyzja histogramu prędkości");
+                            ui.add(egui::Slider::new(&mut self.velocity_precision, 0.2..=2.0));
                         }
 
+                        ui.add_space(16.0);
+
+                        {
+                            /* Histogram energii. */
+                            let mut bars: Vec<Bar> = Vec::new();
+                            let values: Vec<Float> = speeds_and_masses
+                                .iter()
+                                .map(|&(velocity, mass)| mass * velocity.powi(2) * 0.5)
+                                .map(|v| (v / self.energy_precision).floor() * self.energy_precision)
+                                .collect();
+                            for v in values {
+                                let bar = Bar::new((v + self.energy_precision / 2.0) as f64, 1.0);
+                                let index = bars.iter().position(|b| b.argument == bar.argument);
+                                match index {
+                                    None => bars.push(bar),
+                                    Some(i) => bars.get_mut(i).unwrap().value += 1.0,
+                                }
+                            }
 
-                        vector_field.show(ui, |plot_ui| {
-                            plot_ui.arrows(
-                                egui::widgets::plot::Arrows::new(egui::widgets::plot::PlotPoints::from(arrow_origins), egui::widgets::plot::PlotPoints::from(arrow_tips))
-                                .color(Color32::from_rgb(255, 255, 255))
-                            )
-                        });
+                            ui.heading("Histogram energii");
+
+                            let chart = BarChart::new(bars)
+                                .width(self.energy_precision as f64)
+                                .color(Color32::LIGHT_GREEN);
+                            Plot::new("energia")
+                                .width(plot_size)
+                                .height(plot_size)
+                                .allow_drag(false)
+                                .allow_scroll(false)
+                                .allow_zoom(false)
+                                .allow_boxed_zoom(false)
+                                .include_x(0)
+                                .include_x(20.0)
+                                .include_y(0)
+                                .include_y(4.0)
+                                .auto_bounds_x()
+                                .auto_bounds_y()
+                                .show(ui, |plot_ui| plot_ui.bar_chart(chart));
+
+                            ui.label("Precyzja histogramu energii");
+                            ui.add(egui::Slider::new(&mut self.energy_precision, 0.4..=4.0));
+                        }
 
-                    } 
-
-                    {
-                        /* "Histogram" prędkości. */ 
-                        let mut velocities: Vec<_> = self.particles
-                            .iter()
-                            .map(|p| if p.velocity.magnitude().is_finite() { p.velocity.magnitude() } else { 0.0 } )
-                            .collect();
-
-                        /* Floaty nie implementują `Ord` bo NaN != NaN. */
-                        velocities.sort_by(|a, b| a.total_cmp(b));
-
-                        let bars: Vec<_> = velocities
-                            .iter()
-                            .enumerate()
-                            .map(|(i, &v)| Bar::new((i + 1) as f64, v as f64))
-                            .collect();
-
-                        let chart = BarChart::new(bars)
-                            .width(1.0)
-                            .color(Color32::WHITE);
-
-                        ui.heading("Prędkości cząsteczek");
-
-                        Plot::new("predkosc2")
-                            .width(plot_size)
-                            .height(plot_size)
-                            .allow_drag(false)
-                            .allow_scroll(false)
-                            .allow_zoom(false)
-                            .allow_boxed_zoom(false)
-                            .include_y(8.0)
-                            .auto_bounds_y()
-                            .show_axes([false, false])
-                            .show(ui, |plot_ui| plot_ui.bar_chart(chart));
-                    }
+                    });
 
-                    {
-                        /* Wykres środka ciężkości. */
-
-                        /* Jak się okazuje, nie ma chyba analogicznej wielkości dla ładunku, którą
-                         * można policzyć bez ogromnego wysiłku. */
-                        let center_of_mass_plot = Plot::new("center_of_mass")
-                            .view_aspect(1.0)
-                            .width(plot_size)
-                            .height(plot_size)
-                            .allow_drag(false)
-                            .allow_scroll(false)
-                            .allow_zoom(false)
-                            .allow_boxed_zoom(false)
-                            .include_x(0.0)
-                            .include_x(1.0)
-                            .include_y(0.0)
-                            .include_y(1.0)
-                            .show_axes([false, false]);
-
-                        ui.heading("Środek masy");
-
-                        center_of_mass_plot.show(ui, |plot_ui| {
-                            if let Some(center_of_mass) = Particle::center_of_mass(self.particles.iter()) {
-                                plot_ui.points(
-                                    Points::new([center_of_mass.x as f64, center_of_mass.y as f64])
-                                        .radius(4.0)
-                                        .color(Color32::from_rgb(255, 255, 255))
-                                );
+                    ui.vertical(|ui| {
+                        /* Pole jest liczone z cząsteczek 2D, więc w 3D go nie pokazujemy. */
+                        if self.mode == Mode::Plane {
+                            /* Pole wektorowe siły elektrostatycznej (od cząsteczek i zewnętrznego pola). */
+
+                            let vector_field = Plot::new("vector_field")
+                                .view_aspect(1.0)
+                                .width(plot_size)
+                                .height(plot_size)
+                                .allow_drag(false)
+                                .allow_scroll(false)
+                                .allow_zoom(false)
+                                .allow_boxed_zoom(false)
+                                .data_aspect(1.0)
+                                .include_x(limits.min.x)
+                                .include_x(limits.max.x)
+                                .include_y(limits.min.y)
+                                .include_y(limits.max.y)
+                                .show_axes([false, false]);
+
+                            ui.heading("Pole elektryczne");
+
+                            /* Ile wektorów chcemy mieć wzdłuż dłuższego boku. Wzdłuż krótszego jest ich
+                             * proporcjonalnie mniej, żeby odstępy były mniej więcej takie same. */
+                            let resolution = 8.0;
+                            let spacing = domain_size.max() / resolution;
+                            let grid = [
+                                (domain_size.x / spacing).round().max(1.0) as usize,
+                                (domain_size.y / spacing).round().max(1.0) as usize,
+                            ];
+                            let arrow_length = 0.8 * spacing;
+
+                            let mut arrow_origins = Vec::with_capacity(grid[0] * grid[1]);
+                            let mut arrow_tips = Vec::with_capacity(grid[0] * grid[1]);
+
+                            /* Drzewo budujemy raz dla całego wykresu, a nie dla każdej strzałki.
+                             * Przy okresowych brzegach liczymy wprost, tak jak w `Interactions::pair_forces`. */
+                            let tree = match self.force_backend {
+                                _ if self.parameters.limits.periodic() => None,
+                                ForceBackend::Direct => None,
+                                ForceBackend::BarnesHut => Some(QuadTree::new(&self.plane.particles, |p| p.charge)),
+                            };
+
+                            for x in 0..grid[0] {
+                                for y in 0..grid[1] {
+                                    let [x, y] = [
+                                        limits.min.x as f64 + (x as f64 + 0.5) * (domain_size.x as f64 / grid[0] as f64),
+                                        limits.min.y as f64 + (y as f64 + 0.5) * (domain_size.y as f64 / grid[1] as f64),
+                                    ];
+                                    let probe = Particle::new(u32::MAX, x as Float, y as Float, 1.0, 0.5, 0.01);
+                                    let mut force = match tree {
+                                        Some(ref tree) => self.parameters.electro_k * probe.charge
+                                            * tree.field(probe.position, probe.id, self.opening_angle, &self.parameters),
+                                        None => Coulomb.net_force(&probe, &self.plane.particles, &self.parameters, &limits),
+                                    } + probe.charge * self.parameters.electric_field.at(self.time);
+
+                                    if force.magnitude() != 0.0 {
+                                        force = force.normalize() * (force.magnitude().atan() / (consts::PI / 2.0)).powf(4.0) * arrow_length;
+                                    }
+
+                                    arrow_origins.push([x, y]);
+                                    arrow_tips.push([x + force.x as f64, y + force.y as f64]);
+                                }
                             }
 
-                            plot_ui.text(
-                                egui::widgets::plot::Text::new(
-                                    egui::widgets::plot::PlotPoint{x: 0.0, y: 1.0},
-                                    format!("Całkowita masa: {}", self.particles.iter().map(|p| p.mass).sum::<f32>())
+
+                            vector_field.show(ui, |plot_ui| {
+                                plot_ui.arrows(
+                                    egui::widgets::plot::Arrows::new(egui::widgets::plot::PlotPoints::from(arrow_origins), egui::widgets::plot::PlotPoints::from(arrow_tips))
+                                    .color(Color32::from_rgb(255, 255, 255))
                                 )
-                                .anchor(egui::Align2::LEFT_TOP)
-                                .color(Color32::from_rgb(255, 255, 255))
-                            );
-                        });
+                            });
 
-                    }
+                            ui.label(format!(
+                                "Energia potencjalna: {:.3}",
+                                self.plane.interactions.potential_energy(&self.plane.particles, &self.parameters, &limits)
+                            ));
+
+                        } else {
+                            ui.label(format!(
+                                "Energia potencjalna: {:.3}",
+                                self.space.interactions.potential_energy(&self.space.particles, &self.parameters, &self.cube)
+                            ));
+                        }
+
+                        {
+                            /* "Histogram" prędkości. */ 
+                            let mut velocities: Vec<_> = speeds_and_masses
+                                .iter()
+                                .map(|&(v, _)| if v.is_finite() { v } else { 0.0 } )
+                                .collect();
+
+                            /* Floaty nie implementują `Ord` bo NaN != NaN. */
+                            velocities.sort_by(|a, b| a.total_cmp(b));
+
+                            let bars: Vec<_> = velocities
+                                .iter()
+                                .enumerate()
+                                .map(|(i, &v)| Bar::new((i + 1) as f64, v as f64))
+                                .collect();
+
+                            let chart = BarChart::new(bars)
+                                .width(1.0)
+                                .color(Color32::WHITE);
+
+                            ui.heading("Prędkości cząsteczek");
+
+                            Plot::new("predkosc2")
+                                .width(plot_size)
+                                .height(plot_size)
+                                .allow_drag(false)
+                                .allow_scroll(false)
+                                .allow_zoom(false)
+                                .allow_boxed_zoom(false)
+                                .include_y(8.0)
+                                .auto_bounds_y()
+                                .show_axes([false, false])
+                                .show(ui, |plot_ui| plot_ui.bar_chart(chart));
+                        }
+
+                        {
+                            /* Wykres środka ciężkości. */
+
+                            /* Jak się okazuje, nie ma chyba analogicznej wielkości dla ładunku, którą
+                             * można policzyć bez ogromnego wysiłku. */
+                            let center_of_mass_plot = Plot::new("center_of_mass")
+                                .view_aspect(1.0)
+                                .width(plot_size)
+                                .height(plot_size)
+                                .allow_drag(false)
+                                .allow_scroll(false)
+                                .allow_zoom(false)
+                                .allow_boxed_zoom(false)
+                                .data_aspect(1.0)
+                                .include_x(plot_min.x)
+                                .include_x(plot_max.x)
+                                .include_y(plot_min.y)
+                                .include_y(plot_max.y)
+                                .show_axes([false, false]);
+
+                            ui.heading("Środek masy");
+
+                            center_of_mass_plot.show(ui, |plot_ui| {
+                                /* W 3D środek masy jest widziany z tej samej kamery co symulacja. */
+                                let (center_of_mass, total_mass) = match self.mode {
+                                    Mode::Plane => (
+                                        Particle::center_of_mass(self.plane.particles.iter(), &self.parameters.limits)
+                                            .map(|center| [center.x as f64, center.y as f64]),
+                                        self.plane.particles.iter().map(|p| p.mass).sum::<Float>(),
+                                    ),
+                                    Mode::Space => {
+                                        let (cube, camera) = (self.cube, self.camera);
+                                        draw_cube(plot_ui, |position| camera.project(position, &cube).map(|(point, _)| point), &cube);
+                                        (
+                                            Particle3D::center_of_mass(self.space.particles.iter(), &cube)
+                                                .and_then(|center| camera.project(center, &cube))
+                                                .map(|(point, _)| point),
+                                            self.space.particles.iter().map(|p| p.mass).sum::<Float>(),
+                                        )
+                                    }
+                                };
+
+                                if let Some(center_of_mass) = center_of_mass {
+                                    plot_ui.points(
+                                        Points::new(center_of_mass)
+                                            .radius(4.0)
+                                            .color(Color32::from_rgb(255, 255, 255))
+                                    );
+                                }
+
+                                plot_ui.text(
+                                    egui::widgets::plot::Text::new(
+                                        egui::widgets::plot::PlotPoint{x: plot_min.x, y: plot_max.y},
+                                        format!("Całkowita masa: {}", total_mass)
+                                    )
+                                    .anchor(egui::Align2::LEFT_TOP)
+                                    .color(Color32::from_rgb(255, 255, 255))
+                                );
+                            });
+
+                        }
 
                     
 
 
-                });
+                    });
 
-                ui.vertical(|ui| {
-                    /* Wykresy dla śledzonej cząsteczki. */
-                    if let Some(ref tracked_particle) = self.tracked_particle {
-                        /* Ścieżka ruchu. */
-
-                        ui.heading("Tor ruchu");
-
-                        let path_plot = Plot::new("tracked_path")
-                            .view_aspect(1.0)
-                            .width(plot_size)
-                            .height(plot_size)
-                            .allow_drag(false)
-                            .allow_scroll(false)
-                            .allow_zoom(false)
-                            .allow_boxed_zoom(false)
-                            .include_x(0.0)
-                            .include_x(1.0)
-                            .include_y(0.0)
-                            .include_y(1.0)
-                            .show_axes([false, false]);
-
-                        path_plot.show(ui, |plot_ui| {
-                            plot_ui.line(
-                                egui::widgets::plot::Line::new(egui::widgets::plot::PlotPoints::from_iter(
-                                    tracked_particle.path
-                                    .iter()
-                                    .map(|a| [a.x as f64, a.y as f64])
-                                )).color(Color32::from_rgb(255, 255, 255))
-                            )
-                        });
+                    ui.vertical(|ui| self.energy_panel(ui, plot_size));
+
+                    ui.vertical(|ui| self.momentum_panel(ui, plot_size));
+
+                    ui.vertical(|ui| self.pressure_panel(ui, plot_size));
+
+                    ui.vertical(|ui| {
+                        /* Wykresy dla śledzonej cząsteczki. */
+                        if let Some(ref tracked_particle) = self.tracked_particle {
+                            /* Ścieżka ruchu. W trybie 3D widziana z tej samej kamery co symulacja. */
+
+                            ui.heading("Tor ruchu");
+
+                            let path_plot = Plot::new("tracked_path")
+                                .view_aspect(1.0)
+                                .width(plot_size)
+                                .height(plot_size)
+                                .allow_drag(false)
+                                .allow_scroll(false)
+                                .allow_zoom(false)
+                                .allow_boxed_zoom(false)
+                                .data_aspect(1.0)
+                                .include_x(plot_min.x)
+                                .include_x(plot_max.x)
+                                .include_y(plot_min.y)
+                                .include_y(plot_max.y)
+                                .show_axes([false, false]);
+
+                            let (cube, camera) = (self.cube, self.camera);
+                            let segments = match self.mode {
+                                Mode::Plane => path_segments(
+                                    &tracked_particle.path,
+                                    |r| limits.minimum_image(r.xy()) != r.xy(),
+                                    |position| Some([position.x as f64, position.y as f64]),
+                                ),
+                                Mode::Space => path_segments(
+                                    &tracked_particle.path,
+                                    |r| cube.minimum_image(r) != r,
+                                    |position| camera.project(position, &cube).map(|(point, _)| point),
+                                ),
+                            };
+
+                            path_plot.show(ui, |plot_ui| {
+                                if self.mode == Mode::Space {
+                                    draw_cube(plot_ui, |position| camera.project(position, &cube).map(|(point, _)| point), &cube);
+                                }
 
-                        /* Prędkość. */
-
-                        ui.heading("Wartość prędkości");
-
-                        let velocity_plot = Plot::new("tracked_velocity")
-                            .view_aspect(1.0)
-                            .width(plot_size)
-                            .height(plot_size)
-                            .allow_drag(false)
-                            .allow_scroll(false)
-                            .allow_zoom(false)
-                            .allow_boxed_zoom(false)
-                            .include_x(0.0)
-                            .include_x(1.0)
-                            .show_axes([false, true]);
-
-                        velocity_plot.show(ui, |plot_ui| {
-                            plot_ui.line(
-                                egui::widgets::plot::Line::new(egui::widgets::plot::PlotPoints::from_iter(
-                                    tracked_particle.velocity
-                                    .iter()
-                                    .zip(0..TrackedParticle::DATA_POINT_COUNT_VELOCITY)
-                                    .map(|(y, x)| [x as f64 / TrackedParticle::DATA_POINT_COUNT_VELOCITY as f64, y.magnitude() as f64])
-                                )).color(Color32::from_rgb(255, 255, 255))
-                            )
-                        });
+                                for segment in segments {
+                                    plot_ui.line(
+                                        egui::widgets::plot::Line::new(egui::widgets::plot::PlotPoints::from(segment))
+                                            .color(Color32::from_rgb(255, 255, 255))
+                                    );
+                                }
+                            });
 
-                        /* Przyspieszenie. */
-
-                        ui.heading("Wartość przyspieszenia");
-
-                        let acceleration_plot = Plot::new("tracked_acceleration")
-                            .view_aspect(1.0)
-                            .width(plot_size)
-                            .height(plot_size)
-                            .allow_drag(false)
-                            .allow_scroll(false)
-                            .allow_zoom(false)
-                            .allow_boxed_zoom(false)
-                            .include_x(0.0)
-                            .include_x(1.0)
-                            .show_axes([false, true]);
-
-                        acceleration_plot.show(ui, |plot_ui| {
-                            plot_ui.line(
-                                egui::widgets::plot::Line::new(egui::widgets::plot::PlotPoints::from_iter(
-                                    tracked_particle.acceleration
-                                    .iter()
-                                    .zip(0..TrackedParticle::DATA_POINT_COUNT_ACCELERATION)
-                                    .map(|(y, x)| [x as f64 / TrackedParticle::DATA_POINT_COUNT_ACCELERATION as f64, y.magnitude() as f64])
-                                )).color(Color32::from_rgb(255, 255, 255))
-                            )
-                        });
-                    }
+                            /* Prędkość. */
+
+                            ui.heading("Wartość prędkości");
+
+                            let velocity_plot = Plot::new("tracked_velocity")
+                                .view_aspect(1.0)
+                                .width(plot_size)
+                                .height(plot_size)
+                                .allow_drag(false)
+                                .allow_scroll(false)
+                                .allow_zoom(false)
+                                .allow_boxed_zoom(false)
+                                .include_x(0.0)
+                                .include_x(1.0)
+                                .show_axes([false, true]);
+
+                            velocity_plot.show(ui, |plot_ui| {
+                                plot_ui.line(
+                                    egui::widgets::plot::Line::new(egui::widgets::plot::PlotPoints::from_iter(
+                                        tracked_particle.velocity
+                                        .iter()
+                                        .zip(0..TrackedParticle::DATA_POINT_COUNT_VELOCITY)
+                                        .map(|(y, x)| [x as f64 / TrackedParticle::DATA_POINT_COUNT_VELOCITY as f64, *y as f64])
+                                    )).color(Color32::from_rgb(255, 255, 255))
+                                )
+                            });
+
+                            /* Przyspieszenie. */
+
+                            ui.heading("Wartość przyspieszenia");
+
+                            let acceleration_plot = Plot::new("tracked_acceleration")
+                                .view_aspect(1.0)
+                                .width(plot_size)
+                                .height(plot_size)
+                                .allow_drag(false)
+                                .allow_scroll(false)
+                                .allow_zoom(false)
+                                .allow_boxed_zoom(false)
+                                .include_x(0.0)
+                                .include_x(1.0)
+                                .show_axes([false, true]);
+
+                            acceleration_plot.show(ui, |plot_ui| {
+                                plot_ui.line(
+                                    egui::widgets::plot::Line::new(egui::widgets::plot::PlotPoints::from_iter(
+                                        tracked_particle.acceleration
+                                        .iter()
+                                        .zip(0..TrackedParticle::DATA_POINT_COUNT_ACCELERATION)
+                                        .map(|(y, x)| [x as f64 / TrackedParticle::DATA_POINT_COUNT_ACCELERATION as f64, *y as f64])
+                                    )).color(Color32::from_rgb(255, 255, 255))
+                                )
+                            });
+                        }
+                    });
                 });
             });
 
@@ -596,7 +2013,24 @@ impl eframe::App for MyEguiApp {
                 match self.click_action {
                     ClickAction::Add => {
                         if let Some(egui::widgets::plot::PlotPoint{x, y}) = particle_plot_pointer_coordinates {
-                            self.add_user_particles(x as f32, y as f32, self.user_particle_input_state);
+                            self.plane.add_user_particles(
+                                &self.parameters.limits,
+                                Vect::new(x as Float, y as Float),
+                                self.user_particle_input_state,
+                                &self.user_bond_input_state,
+                                &mut self.next_particle_id,
+                                &mut self.rng,
+                            );
+                        }
+                        if let Some(position) = space_pointer_position {
+                            self.space.add_user_particles(
+                                &self.cube,
+                                position,
+                                self.user_particle_input_state,
+                                &self.user_bond_input_state,
+                                &mut self.next_particle_id,
+                                &mut self.rng,
+                            );
                         }
                     },
                     ClickAction::Track => {
@@ -604,11 +2038,38 @@ impl eframe::App for MyEguiApp {
                             self.tracked_particle = Some(TrackedParticle::new(id));
                         }
                     }
+                    ClickAction::Bond => {
+                        if let Some(id) = selected_particle_id {
+                            match self.bond_start {
+                                Some(start) if start != id => {
+                                    match self.mode {
+                                        Mode::Plane => self.plane.add_bond(start, id, &self.user_bond_input_state, &self.parameters.limits),
+                                        Mode::Space => self.space.add_bond(start, id, &self.user_bond_input_state, &self.cube),
+                                    }
+                                    self.bond_start = None;
+                                }
+                                _ => self.bond_start = Some(id),
+                            }
+                        }
+                    }
+                    ClickAction::Pin => {
+                        if let Some(id) = selected_particle_id {
+                            match self.mode {
+                                Mode::Plane => self.plane.toggle_fixed(id),
+                                Mode::Space => self.space.toggle_fixed(id),
+                            }
+                        }
+                    }
+                    ClickAction::Obstacle => {
+                        if let Some(egui::widgets::plot::PlotPoint{x, y}) = particle_plot_pointer_coordinates {
+                            self.obstacle_draft.push(Vect::new(x as Float, y as Float));
+                        }
+                    }
                 }
             }
 
 
-            let d_time = ui.input().stable_dt * self.time_multiplier;
+            let d_time = ui.input().stable_dt as Float * self.time_multiplier;
             self.simulation(d_time);
             ui.ctx().request_repaint()
         });
@@ -619,17 +2080,51 @@ impl eframe::App for MyEguiApp {
 #[derive(Copy, Clone)]
 struct UserParticleInputState {
     count: u32,
-    charge: f32,
-    mass: f32,
+    charge: Float,
+    mass: Float,
+    radius: Float,
+    /* Czy wstawiane cząsteczki mają być połączone wiązaniami w łańcuch. */
+    chain: bool,
+    /* Czy co druga cząsteczka ma mieć przeciwny ładunek. */
+    alternate_charges: bool,
+    /* Czy wstawiane cząsteczki mają być nieruchome. */
+    fixed: bool,
+}
+
+/* Parametry nowych wiązań tworzonych przez użytkownika. */
+#[derive(Copy, Clone)]
+struct UserBondInputState {
+    rigid: bool,
+    stiffness: Float,
+    damping: Float,
+}
+
+impl UserBondInputState {
+    /* Wiązanie między `a` i `b` o długości równej ich aktualnej odległości. */
+    fn bond<const D: usize>(&self, a: &Body<D>, b: &Body<D>, domain: &impl Domain<D>) -> Bond {
+        let length = domain.minimum_image(a.position - b.position).magnitude();
+
+        return Bond {
+            a: a.id,
+            b: b.id,
+            kind: if self.rigid {
+                BondKind::Rigid { length }
+            } else {
+                BondKind::Spring { rest_length: length, stiffness: self.stiffness, damping: self.damping }
+            },
+        };
+    }
 }
 
 /* Wszystkie punkty danych potrzebne do zrobienia wykresów dla śledzonej cząsteczki. */
 #[derive(Debug)]
 struct TrackedParticle {
     id: u32,
-    path: std::collections::VecDeque<Vect>,
-    velocity: std::collections::VecDeque<Vect>,
-    acceleration: std::collections::VecDeque<Vect>,
+    /* W trybie 2D z zerową współrzędną z. */
+    path: std::collections::VecDeque<Vect3>,
+    /* Wartości prędkości i przyspieszenia. */
+    velocity: std::collections::VecDeque<Float>,
+    acceleration: std::collections::VecDeque<Float>,
 }
 
 impl TrackedParticle {
@@ -646,4 +2141,162 @@ impl TrackedParticle {
             acceleration: std::collections::VecDeque::with_capacity(Self::DATA_POINT_COUNT_ACCELERATION),
         };
     }
+
+    /* Dopisuje dane z jednej klatki, usuwając najstarsze, jeśli jest ich już za dużo. */
+    fn record(&mut self, position: Vect3, velocity: Float, acceleration: Float) {
+        if self.path.len() == Self::DATA_POINT_COUNT_PATH {
+            self.path.pop_front();
+        }
+
+        if self.velocity.len() == Self::DATA_POINT_COUNT_VELOCITY {
+            self.velocity.pop_front();
+        }
+
+        if self.acceleration.len() == Self::DATA_POINT_COUNT_ACCELERATION {
+            self.acceleration.pop_front();
+        }
+
+        self.path.push_back(position);
+        self.velocity.push_back(velocity);
+        self.acceleration.push_back(acceleration);
+    }
+}
+
+/* Ile pikseli na ekranie zajmuje jednostka długości na wykresie. */
+fn pixels_per_unit(plot_ui: &egui::widgets::plot::PlotUi) -> f32 {
+    return plot_ui.screen_from_plot(egui::widgets::plot::PlotPoint{x: 1.0, y: 0.0}).x
+        - plot_ui.screen_from_plot(egui::widgets::plot::PlotPoint{x: 0.0, y: 0.0}).x;
+}
+
+/* Id cząsteczki najbliższej kursorowi spośród par (id, położenie na wykresie), o ile jest
+ * wystarczająco blisko.
+ *
+ * Jeśli kursor znajduje się w co najwyżej takiej odległości od środka cząsteczki, to uznajemy,
+ * że jest na cząsteczce. Podajemy ją w pikselach, bo w jednostkach symulacji zależałaby
+ * od wielkości obszaru. */
+fn particle_under_pointer(
+    particles: impl Iterator<Item = (u32, [f64; 2])>,
+    pointer: egui::widgets::plot::PlotPoint,
+    pixels_per_unit: f32,
+) -> Option<u32> {
+    let selection_radius = 12.0 / pixels_per_unit as f64;
+    let pointer = na::Vector2::<f64>::new(pointer.x, pointer.y);
+
+    /* Szukamy cząsteczki najbliżej kursora i patrzymy, czy jest w promieniu. */
+    return particles
+        .map(|(id, position)| (id, (na::Vector2::from(position) - pointer).magnitude()))
+        .min_by(|(_, d1), (_, d2)| d1.total_cmp(d2))
+        .filter(|(_, d)| d <= &selection_radius)
+        .map(|(id, _)| id);
+}
+
+/* Tor ruchu podzielony na kawałki do narysowania liniami.
+ *
+ * Przy okresowych brzegach tor dzielimy w miejscach przejścia przez brzeg (`jumped` dostaje
+ * przesunięcie między kolejnymi punktami), żeby nie rysować linii przez całe pudełko.
+ * `project` zamienia punkt na współrzędne wykresu; punkty, których nie widać, też dzielą tor. */
+fn path_segments(
+    path: &std::collections::VecDeque<Vect3>,
+    jumped: impl Fn(Vect3) -> bool,
+    project: impl Fn(Vect3) -> Option<[f64; 2]>,
+) -> Vec<Vec<[f64; 2]>> {
+    let mut segments: Vec<Vec<[f64; 2]>> = vec![vec![]];
+
+    for (i, &position) in path.iter().enumerate() {
+        let point = project(position);
+
+        if i > 0 && (jumped(position - path[i - 1]) || point.is_none()) && !segments.last().unwrap().is_empty() {
+            segments.push(vec![]);
+        }
+
+        if let Some(point) = point {
+            segments.last_mut().unwrap().push(point);
+        }
+    }
+
+    return segments;
+}
+
+/* Cząsteczka jako punkt o promieniu `radius` pikseli. Nieruchome cząsteczki są kwadratami
+ * z krzyżykiem, bo same kwadraciki przy kilku pikselach wyglądają prawie jak kółka. */
+fn draw_particle(plot_ui: &mut egui::widgets::plot::PlotUi, point: [f64; 2], radius: f32, color: Color32, fixed: bool) {
+    plot_ui.points(
+        Points::new(point)
+            .radius(radius)
+            .color(color)
+            .shape(if fixed { MarkerShape::Square } else { MarkerShape::Circle }),
+    );
+
+    if fixed {
+        plot_ui.points(
+            Points::new(point)
+                .radius(radius.max(4.0))
+                .color(Color32::DARK_GRAY)
+                .shape(MarkerShape::Plus),
+        );
+    }
+}
+
+/* Wiązania z `world`, rzutowane przez `project`. Przy okresowych brzegach wiązanie
+ * rysujemy do najbliższego obrazu drugiej cząsteczki, a nie przez całe pudełko. */
+fn draw_bonds<const D: usize>(
+    plot_ui: &mut egui::widgets::plot::PlotUi,
+    world: &World<D>,
+    domain: &impl Domain<D>,
+    project: impl Fn(VectN<D>) -> Option<[f64; 2]>,
+) {
+    for bond in &world.bonds {
+        let a = world.particles.iter().find(|p| p.id == bond.a);
+        let b = world.particles.iter().find(|p| p.id == bond.b);
+
+        if let (Some(a), Some(b)) = (a, b) {
+            let b_position = a.position + domain.minimum_image(b.position - a.position);
+            let (width, color) = match bond.kind {
+                BondKind::Spring { .. } => (1.5, Color32::GRAY),
+                BondKind::Rigid { .. } => (3.0, Color32::LIGHT_GRAY),
+            };
+
+            if let (Some(a), Some(b)) = (project(a.position), project(b_position)) {
+                plot_ui.line(
+                    egui::widgets::plot::Line::new(egui::widgets::plot::PlotPoints::from(vec![a, b]))
+                        .width(width)
+                        .color(color)
+                );
+            }
+        }
+    }
+}
+
+/* Przeszkody na płaszczyźnie xy. */
+fn draw_obstacles(plot_ui: &mut egui::widgets::plot::PlotUi, obstacles: &[Obstacle]) {
+    for obstacle in obstacles {
+        let points: Vec<[f64; 2]> = obstacle.points.iter().map(|p| [p.x as f64, p.y as f64]).collect();
+        if obstacle.closed {
+            plot_ui.polygon(
+                egui::widgets::plot::Polygon::new(egui::widgets::plot::PlotPoints::from(points))
+                    .color(Color32::LIGHT_GRAY)
+                    .fill_alpha(0.3)
+                    .width(2.0)
+            );
+        } else {
+            plot_ui.line(
+                egui::widgets::plot::Line::new(egui::widgets::plot::PlotPoints::from(points))
+                    .color(Color32::LIGHT_GRAY)
+                    .width(2.0)
+            );
+        }
+    }
+}
+
+/* Szkielet sześcianu trybu 3D, rzutowany przez `project`. */
+fn draw_cube(plot_ui: &mut egui::widgets::plot::PlotUi, project: impl Fn(Vect3) -> Option<[f64; 2]>, cube: &Cube) {
+    for (a, b) in projection::cube_edges(cube) {
+        if let (Some(a), Some(b)) = (project(a), project(b)) {
+            plot_ui.line(
+                egui::widgets::plot::Line::new(egui::widgets::plot::PlotPoints::from(vec![a, b]))
+                    .color(Color32::GRAY)
+                    .width(1.0)
+            );
+        }
+    }
 }
@@ -0,0 +1,146 @@
+use std::collections::VecDeque;
+
+use crate::energy::push;
+use crate::parameters::Domain;
+use crate::particle::{Body, Float, VectN};
+use crate::particle3d::Vect3;
+
+/* Wektor z przestrzeni o `D` wymiarach jako wektor 3D (w 2D ze składową z równą zero),
+ * żeby oba tryby mogły dzielić te same wykresy. */
+fn to_3d<const D: usize>(v: VectN<D>) -> Vect3 {
+    return v.fixed_resize::<3, 1>(0.0);
+}
+
+/* Całkowity pęd ruchomych cząsteczek. */
+pub fn linear_momentum<const D: usize>(particles: &[Body<D>]) -> Vect3 {
+    return particles
+        .iter()
+        .filter(|p| !p.fixed)
+        .map(|p| to_3d(p.mass * p.velocity))
+        .filter(|m| m.iter().all(|x| x.is_finite()))
+        .fold(Vect3::zeros(), |acc, m| acc + m);
+}
+
+/* Moment pędu ruchomych cząsteczek względem ich środka masy (w 2D tylko składowa z).
+ * Przy okresowych ścianach położenia względem środka bierzemy z konwencji najbliższego obrazu. */
+pub fn angular_momentum<const D: usize>(particles: &[Body<D>], domain: &impl Domain<D>) -> Vect3 {
+    let moving = || particles.iter().filter(|p| !p.fixed);
+
+    let Some(center) = Body::center_of_mass(moving(), domain) else {
+        return Vect3::zeros();
+    };
+
+    return moving()
+        .map(|p| p.mass * to_3d(domain.minimum_image(p.position - center)).cross(&to_3d(p.velocity)))
+        .filter(|l| l.iter().all(|x| x.is_finite()))
+        .fold(Vect3::zeros(), |acc, l| acc + l);
+}
+
+/* Pęd przekazany ruchomym cząsteczkom od początku pomiaru, rozdzielony według źródła.
+ * Suma wektorów (`total`) to zmiana całkowitego pędu, o ile w międzyczasie nie dodawano
+ * cząsteczek z niezerową prędkością. */
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub struct Impulses {
+    /* Popęd od odbić od ścian razem z pędem wyniesionym przez pochłonięte cząsteczki. */
+    pub walls: Vect3,
+    pub drag: Vect3,
+    /* Pola zewnętrzne (grawitacja, pole elektryczne i magnetyczne), przeszkody i termostat. */
+    pub external: Vect3,
+    /* Siły od przypiętych cząsteczek, zderzenia z nimi i sztywne wiązania. */
+    pub constraints: Vect3,
+    /* Ile pędu cząsteczki przekazały sobie nawzajem: połowa sumy wartości sił między nimi razy czas. */
+    pub exchanged: Float,
+    /* Suma tych sił razy czas. Z trzeciej zasady dynamiki powinna być zerowa, więc to, co tu
+     * narasta, to błąd sumowania par (np. przybliżenie Barnesa-Huta). */
+    pub imbalance: Vect3,
+}
+
+impl Impulses {
+    /* Dolicza wymianę pędu z sił par `pair_forces` działających przez `d_time`, razem z tym,
+     * czy cząsteczka, na którą działa siła, jest przypięta. Zwraca popęd, który te siły
+     * przekazały ruchomym cząsteczkom.
+     *
+     * Siły działające na przypięte cząsteczki są przeciwne do sił, którymi one działają
+     * na ruchome, więc z nich bierzemy popęd od przypiętych (`constraints`). */
+    pub fn add_pair_forces(&mut self, pair_forces: impl Iterator<Item = (Vect3, bool)>, d_time: Float) -> Vect3 {
+        let mut moving = Vect3::zeros();
+
+        for (force, fixed) in pair_forces.filter(|(f, _)| f.iter().all(|x| x.is_finite())) {
+            self.exchanged += 0.5 * force.magnitude() * d_time;
+            self.imbalance += force * d_time;
+
+            if fixed {
+                self.constraints -= force * d_time;
+            } else {
+                moving += force * d_time;
+            }
+        }
+
+        return moving;
+    }
+
+    /* Suma wszystkich popędów przekazanych ruchomym cząsteczkom. */
+    pub fn total(&self) -> Vect3 {
+        return self.walls + self.drag + self.external + self.constraints + self.imbalance;
+    }
+}
+
+/* Przebieg w czasie pędu, momentu pędu i popędów z `Impulses`.
+ * Składowe wektorów trzymamy osobno (x, y, z), a popędy jako wartości. */
+pub struct MomentumHistory {
+    pub linear: [VecDeque<[f64; 2]>; 3],
+    pub angular: [VecDeque<[f64; 2]>; 3],
+    pub walls: VecDeque<[f64; 2]>,
+    pub drag: VecDeque<[f64; 2]>,
+    pub external: VecDeque<[f64; 2]>,
+    pub constraints: VecDeque<[f64; 2]>,
+    pub exchanged: VecDeque<[f64; 2]>,
+    pub imbalance: VecDeque<[f64; 2]>,
+}
+
+impl MomentumHistory {
+    /* Dla ilu chwil czasu chcemy trzymać wartości. */
+    pub const DATA_POINT_COUNT: usize = 1024;
+
+    pub fn new() -> Self {
+        let points = || VecDeque::with_capacity(Self::DATA_POINT_COUNT);
+
+        return Self {
+            linear: [points(), points(), points()],
+            angular: [points(), points(), points()],
+            walls: points(),
+            drag: points(),
+            external: points(),
+            constraints: points(),
+            exchanged: points(),
+            imbalance: points(),
+        };
+    }
+
+    pub fn clear(&mut self) {
+        *self = Self::new();
+    }
+
+    pub fn record(&mut self, time: Float, linear: Vect3, angular: Vect3, impulses: &Impulses) {
+        let time = time as f64;
+        let capacity = Self::DATA_POINT_COUNT;
+
+        for axis in 0..3 {
+            push(&mut self.linear[axis], [time, linear[axis] as f64], capacity);
+            push(&mut self.angular[axis], [time, angular[axis] as f64], capacity);
+        }
+
+        push(&mut self.walls, [time, impulses.walls.magnitude() as f64], capacity);
+        push(&mut self.drag, [time, impulses.drag.magnitude() as f64], capacity);
+        push(&mut self.external, [time, impulses.external.magnitude() as f64], capacity);
+        push(&mut self.constraints, [time, impulses.constraints.magnitude() as f64], capacity);
+        push(&mut self.exchanged, [time, impulses.exchanged as f64], capacity);
+        push(&mut self.imbalance, [time, impulses.imbalance.magnitude() as f64], capacity);
+    }
+}
+
+impl Default for MomentumHistory {
+    fn default() -> Self {
+        return Self::new();
+    }
+}
@@ -0,0 +1,178 @@
+use crate::particle::{from_plane, plane, Body, Float, Vect, VectN};
+
+/* Nieruchoma przeszkoda wewnątrz obszaru symulacji: łamana albo wielokąt.
+ * Cząsteczki odbijają się od każdego jej odcinka jak od ściany. */
+#[derive(Clone, PartialEq, Debug)]
+pub struct Obstacle {
+    /* Kolejne wierzchołki. */
+    pub points: Vec<Vect>,
+    /* Czy ostatni wierzchołek jest połączony z pierwszym. */
+    pub closed: bool,
+    /* Współczynnik restytucji przy odbiciu, tak jak dla ścian pudełka. */
+    pub restitution: Float,
+}
+
+impl Obstacle {
+    pub fn segment(a: Vect, b: Vect) -> Self {
+        return Self::polyline(vec![a, b]);
+    }
+
+    pub fn polyline(points: Vec<Vect>) -> Self {
+        return Self { points, closed: false, restitution: 1.0 };
+    }
+
+    pub fn polygon(points: Vec<Vect>) -> Self {
+        return Self { points, closed: true, restitution: 1.0 };
+    }
+
+    pub fn rectangle(min: Vect, max: Vect) -> Self {
+        return Self::polygon(vec![min, Vect::new(max.x, min.y), max, Vect::new(min.x, max.y)]);
+    }
+
+    /* Ta sama przeszkoda po przeniesieniu prostokąta `from` na prostokąt `to` (oba jako
+     * najmniejszy i największy róg), np. z obszaru 2D na ścianę sześcianu w trybie 3D. */
+    pub fn mapped(&self, from: (Vect, Vect), to: (Vect, Vect)) -> Self {
+        let scale = (to.1 - to.0).component_div(&(from.1 - from.0));
+        let points = self.points.iter().map(|p| to.0 + (p - from.0).component_mul(&scale)).collect();
+        return Self { points, ..*self };
+    }
+
+    /* Końce wszystkich odcinków przeszkody. */
+    pub fn segments(&self) -> impl Iterator<Item = (Vect, Vect)> + '_ {
+        let n = self.points.len();
+        let count = if self.closed && n > 2 { n } else { n.saturating_sub(1) };
+
+        return (0..count).map(move |i| (self.points[i], self.points[(i + 1) % n]));
+    }
+}
+
+/* Iloczyn wektorowy w 2D (składowa z). */
+fn cross(a: Vect, b: Vect) -> Float {
+    return a.x * b.y - a.y * b.x;
+}
+
+/* Punkt odcinka ab najbliższy punktowi `point`. */
+fn closest_point(a: Vect, b: Vect, point: Vect) -> Vect {
+    let ab = b - a;
+    let len_sq = ab.magnitude_squared();
+
+    if len_sq == 0.0 {
+        return a;
+    }
+
+    return a + ab * ((point - a).dot(&ab) / len_sq).clamp(0.0, 1.0);
+}
+
+/* Część drogi p0p1 (od 0 do 1), po której przecina ona odcinek ab, albo `None`, jeśli go nie przecina. */
+fn crossing(p0: Vect, p1: Vect, a: Vect, b: Vect) -> Option<Float> {
+    let (d, e) = (p1 - p0, b - a);
+    let denominator = cross(d, e);
+
+    /* Odcinki równoległe. Nawet jeśli leżą na jednej prostej, to cząsteczka sunie wzdłuż
+     * przeszkody i tym zajmie się sprawdzanie nachodzenia. */
+    if denominator == 0.0 {
+        return None;
+    }
+
+    let t = cross(a - p0, e) / denominator;
+    let u = cross(a - p0, d) / denominator;
+
+    return if (0.0..=1.0).contains(&t) && (0.0..=1.0).contains(&u) { Some(t) } else { None };
+}
+
+/* Odbija prędkość od powierzchni o normalnej `normal`, jeśli cząsteczka w nią leci. */
+fn reflect<const D: usize>(p: &mut Body<D>, normal: Vect, restitution: Float) {
+    let normal = from_plane::<D>(normal);
+    let normal_velocity = p.velocity.dot(&normal);
+
+    if normal_velocity < 0.0 {
+        p.velocity -= (1.0 + restitution) * normal_velocity * normal;
+    }
+}
+
+/* Odbija cząsteczki od przeszkód.
+ *
+ * `previous` to położenia cząsteczek sprzed ostatniego kroku (w tej samej kolejności co
+ * `particles`). Bez nich szybka cząsteczka mogłaby w jednym kroku przeskoczyć cienką
+ * przeszkodę, więc najpierw sprawdzamy, czy droga środka przecięła któryś odcinek,
+ * a dopiero potem, czy cząsteczka na niego nachodzi.
+ *
+ * Przy każdym odbiciu bierzemy tylko najwcześniejsze przecięcie i sprawdzamy dalej już
+ * odbitą drogę. Inaczej cząsteczka lecąca blisko wspólnego wierzchołka dwóch odcinków
+ * odbiłaby się od obu naraz i mogła wrócić przez przeszkodę.
+ *
+ * W 3D przeszkody są graniastosłupami przez całą głębokość sześcianu (tak jak obszary pola
+ * magnetycznego), więc wszystko liczymy na rzucie na płaszczyznę xy, a z się nie zmienia. */
+pub fn resolve_obstacles<const D: usize>(obstacles: &[Obstacle], particles: &mut [Body<D>], previous: &[VectN<D>]) {
+    /* Ile odbić może być w jednym kroku (więcej niż jedno tylko w wąskich kątach). */
+    const MAX_BOUNCES: usize = 4;
+    /* Przecięcia na samym początku odbitej drogi to odcinek, od którego się właśnie odbiła,
+     * albo sąsiedni, który ma z nim wspólny wierzchołek. */
+    const MIN_CROSSING: Float = 1e-4;
+
+    if obstacles.is_empty() {
+        return;
+    }
+
+    let segments: Vec<(Vect, Vect, Float)> = obstacles
+        .iter()
+        .flat_map(|obstacle| obstacle.segments().map(|(a, b)| (a, b, obstacle.restitution)))
+        .filter(|(a, b, _)| a != b)
+        .collect();
+
+    for (p, &previous) in particles.iter_mut().zip(previous) {
+        if p.fixed || !p.position.magnitude_squared().is_finite() {
+            continue;
+        }
+
+        let mut start = plane(previous);
+        let mut position = plane(p.position);
+        let mut last_hit = None;
+
+        for _ in 0..MAX_BOUNCES {
+            let hit = segments
+                .iter()
+                .enumerate()
+                .filter(|&(i, _)| Some(i) != last_hit)
+                .filter_map(|(i, &(a, b, restitution))| {
+                    let t = crossing(start, position, a, b)?;
+                    let after_bounce = last_hit.is_some() && t < MIN_CROSSING;
+                    return if after_bounce { None } else { Some((t, i, a, b, restitution)) };
+                })
+                .min_by(|x, y| x.0.total_cmp(&y.0));
+
+            let Some((t, i, a, b, restitution)) = hit else {
+                break;
+            };
+
+            /* Normalna odcinka skierowana w stronę, z której przyleciała cząsteczka. */
+            let mut normal = Vect::new(a.y - b.y, b.x - a.x).normalize();
+            if (start - a).dot(&normal) < 0.0 {
+                normal = -normal;
+            }
+
+            /* Położenie odbijamy względem prostej (przy restytucji mniejszej niż 1
+             * odpowiednio bliżej niej), czyli tak, jakby odbicie nastąpiło w trakcie kroku. */
+            start += t * (position - start);
+            let depth = (position - a).dot(&normal);
+            position -= (1.0 + restitution) * depth * normal;
+            reflect(p, normal, restitution);
+            last_hit = Some(i);
+        }
+
+        for &(a, b, restitution) in &segments {
+            let closest = closest_point(a, b, position);
+            let r = position - closest;
+            let distance = r.magnitude();
+
+            if 0.0 < distance && distance < p.radius {
+                let normal = r / distance;
+                position = closest + normal * p.radius;
+                reflect(p, normal, restitution);
+            }
+        }
+
+        p.position[0] = position.x;
+        p.position[1] = position.y;
+    }
+}
@@ -0,0 +1,899 @@
+use crate::obstacle::Obstacle;
+use crate::particle::{consts, Float, Vect, VectN};
+
+/* Co się dzieje z cząsteczką, która doleciała do ściany. */
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum WallBehaviour {
+    /* Odbicie ze współczynnikiem restytucji `Wall::restitution`. */
+    Reflect,
+    /* Cząsteczka znika (i jest liczona w UI). */
+    Absorb,
+    /* Cząsteczka pojawia się po przeciwnej stronie pudełka. */
+    Wrap,
+    /* Cząsteczka odbija się z nową, losową prędkością z rozkładu Maxwella dla temperatury
+     * `Wall::temperature`, jakby na chwilę wymieniła energię ze ścianą. */
+    Thermal,
+}
+
+impl WallBehaviour {
+    pub const ALL: [WallBehaviour; 4] = [WallBehaviour::Reflect, WallBehaviour::Absorb, WallBehaviour::Wrap, WallBehaviour::Thermal];
+
+    pub fn name(&self) -> &'static str {
+        return match self {
+            WallBehaviour::Reflect => "Odbicie",
+            WallBehaviour::Absorb => "Pochłanianie",
+            WallBehaviour::Wrap => "Okresowa",
+            WallBehaviour::Thermal => "Termiczna",
+        };
+    }
+}
+
+/* Ustawienia jednej ściany. `restitution` i `temperature` są używane tylko przy
+ * odpowiednim `behaviour`, ale trzymamy je zawsze, żeby nie ginęły przy przełączaniu w UI. */
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Wall {
+    pub behaviour: WallBehaviour,
+    /* 1 to odbicie sprężyste, 0 to cząsteczka traci całą prędkość prostopadłą do ściany. */
+    pub restitution: Float,
+    /* Temperatura ściany termicznej (stała Boltzmanna jest równa 1). */
+    pub temperature: Float,
+}
+
+impl Wall {
+    pub const REFLECT: Wall = Wall { behaviour: WallBehaviour::Reflect, restitution: 1.0, temperature: 1.0 };
+    pub const WRAP: Wall = Wall { behaviour: WallBehaviour::Wrap, ..Wall::REFLECT };
+}
+
+/* Współrzędne pudełka ograniczającego ruch cząsteczek. */
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Limits {
+    pub min: Vect,
+    pub max: Vect,
+    /* Ściany w kolejności: lewa, prawa, dolna, górna (czyli indeks to 2 * oś + 0 dla `min`
+     * albo 1 dla `max`). */
+    pub walls: [Wall; 4],
+}
+
+impl Limits {
+    pub const WALL_NAMES: [&'static str; 4] = ["Lewa", "Prawa", "Dolna", "Górna"];
+    /* Dozwolone długości boków obszaru. Przy bardziej wydłużonych obszarach wykres
+     * symulacji robi się za wąski, żeby coś na nim zobaczyć. */
+    pub const SIZE_RANGE: std::ops::RangeInclusive<Float> = 0.1..=10.0;
+
+    /* Punkt obszaru podany w ułamkach jego szerokości i wysokości, licząc od lewego dolnego rogu. */
+    pub fn point(&self, x: Float, y: Float) -> Vect {
+        return self.min + self.size().component_mul(&Vect::new(x, y));
+    }
+
+    /* Zmienia wymiary obszaru, zostawiając lewy dolny róg w miejscu. */
+    pub fn set_size(&mut self, width: Float, height: Float) {
+        self.max = self.min + Vect::new(width, height);
+    }
+}
+
+impl Domain<2> for Limits {
+    fn bounds(&self) -> (Vect, Vect) {
+        return (self.min, self.max);
+    }
+
+    fn wall(&self, index: usize) -> Wall {
+        return self.walls[index];
+    }
+}
+
+/* Prostopadłościenny obszar, w którym poruszają się cząsteczki: `Limits` na płaszczyźnie
+ * albo `Cube` w trybie 3D. Ściany mają indeksy 2 * oś + 0 dla ściany przy `min` albo 1 dla
+ * ściany przy `max`, tak samo jak w `Limits::walls`. */
+pub trait Domain<const D: usize> {
+    /* Najmniejsze i największe współrzędne punktów obszaru. */
+    fn bounds(&self) -> (VectN<D>, VectN<D>);
+
+    fn wall(&self, index: usize) -> Wall;
+
+    fn size(&self) -> VectN<D> {
+        let (min, max) = self.bounds();
+        return max - min;
+    }
+
+    /* Skala długości obszaru, czyli najkrótszy bok. Rozrzut wstawianych cząsteczek i dozwolone
+     * promienie są dobrane dla obszaru o boku 1, więc przy innych rozmiarach skalujemy je przez nią. */
+    fn extent(&self) -> Float {
+        return self.size().min();
+    }
+
+    fn contains(&self, position: VectN<D>) -> bool {
+        let (min, max) = self.bounds();
+        return (0..D).all(|axis| (min[axis]..=max[axis]).contains(&position[axis]));
+    }
+
+    /* Oś jest okresowa, gdy obie jej ściany są `WallBehaviour::Wrap`. Przy jednej takiej
+     * ścianie cząsteczki przez nią przechodzą, ale odległości liczymy normalnie, bo
+     * przez drugą ścianę i tak nie widać. */
+    fn periodic_axes(&self) -> [bool; D] {
+        let wrap = |wall: usize| self.wall(wall).behaviour == WallBehaviour::Wrap;
+        return std::array::from_fn(|axis| wrap(2 * axis) && wrap(2 * axis + 1));
+    }
+
+    /* Czy choć jedna oś jest okresowa. */
+    fn periodic(&self) -> bool {
+        return self.periodic_axes().contains(&true);
+    }
+
+    /* Wektor `r` między dwiema cząsteczkami zamieniony na wektor do najbliższego obrazu
+     * drugiej cząsteczki (konwencja minimalnego obrazu). Na osiach nieokresowych się nie zmienia.
+     *
+     * Dla sił dalekozasięgowych to tylko przybliżenie (dokładnie trzeba by zsumować wszystkie
+     * obrazy, np. metodą Ewalda), ale przy ładunkach obu znaków i tak się głównie ekranują. */
+    fn minimum_image(&self, mut r: VectN<D>) -> VectN<D> {
+        let size = self.size();
+
+        for (axis, periodic) in self.periodic_axes().into_iter().enumerate() {
+            if periodic {
+                r[axis] -= size[axis] * (r[axis] / size[axis]).round();
+            }
+        }
+
+        return r;
+    }
+
+    /* Pole ściany prostopadłej do osi `axis` (w 2D jej długość), do liczenia ciśnienia. */
+    fn wall_area(&self, axis: usize) -> Float {
+        let size = self.size();
+        return (0..D).filter(|other| *other != axis).map(|other| size[other]).product();
+    }
+}
+
+/* Jednostka długości pokazywana w UI.
+ *
+ * Symulacja jest bezwymiarowa, więc zmiana jednostki niczego nie przelicza, tylko mówi,
+ * jak czytać liczby (stałe fizyczne trzeba wtedy dobierać w tych samych jednostkach). */
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum LengthUnit {
+    None,
+    Nanometre,
+    Micrometre,
+    Millimetre,
+    Centimetre,
+    Metre,
+}
+
+impl LengthUnit {
+    pub const ALL: [LengthUnit; 6] = [
+        LengthUnit::None,
+        LengthUnit::Nanometre,
+        LengthUnit::Micrometre,
+        LengthUnit::Millimetre,
+        LengthUnit::Centimetre,
+        LengthUnit::Metre,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        return match self {
+            LengthUnit::None => "Bez jednostek",
+            LengthUnit::Nanometre => "nm",
+            LengthUnit::Micrometre => "µm",
+            LengthUnit::Millimetre => "mm",
+            LengthUnit::Centimetre => "cm",
+            LengthUnit::Metre => "m",
+        };
+    }
+
+    /* Tekst dopisywany za liczbami, np. na suwakach. */
+    pub fn suffix(&self) -> String {
+        return match self {
+            LengthUnit::None => String::new(),
+            unit => format!(" {}", unit.name()),
+        };
+    }
+}
+
+/* Sposób łagodzenia oddziaływania elektrostatycznego na bardzo małych odległościach. */
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Softening {
+    /* Siła równa zero, gdy kwadrat odległości jest mniejszy niż `eps`, a poza tym zwykłe 1/r.
+     * Siła ma wtedy nieciągłość i cząsteczki potrafią wystrzelić z bliskich spotkań. */
+    HardCutoff,
+    /* Zmiękczenie Plummera: r^2 zastępujemy przez r^2 + h^2. */
+    Plummer,
+    /* Wielomian wewnątrz promienia h, sklejony tak, że siła i jej pochodna są ciągłe. */
+    Spline,
+}
+
+impl Softening {
+    pub const ALL: [Softening; 3] = [Softening::HardCutoff, Softening::Plummer, Softening::Spline];
+
+    pub fn name(&self) -> &'static str {
+        return match self {
+            Softening::HardCutoff => "Obcięcie",
+            Softening::Plummer => "Plummer",
+            Softening::Spline => "Spline",
+        };
+    }
+}
+
+/* Co się dzieje, gdy dwie cząsteczki się dotkną. */
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum CollisionMode {
+    /* Cząsteczki przez siebie przelatują, jak punkty. */
+    None,
+    /* Cząsteczki odbijają się jak twarde dyski, ze współczynnikiem restytucji `restitution`. */
+    Bounce,
+    /* Dotykające się cząsteczki łączą się w jedną nową, z zachowaniem masy, ładunku i pędu. */
+    Merge,
+}
+
+impl CollisionMode {
+    pub const ALL: [CollisionMode; 3] = [CollisionMode::None, CollisionMode::Bounce, CollisionMode::Merge];
+
+    pub fn name(&self) -> &'static str {
+        return match self {
+            CollisionMode::None => "Brak",
+            CollisionMode::Bounce => "Odbicie",
+            CollisionMode::Merge => "Łączenie",
+        };
+    }
+}
+
+/* Co zrobić, gdy połączona cząsteczka miałaby ładunek albo masę spoza przedziałów
+ * z `Particle::valid` (ładunek z [-1, 1], masa z (0, 1]). */
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum MergePolicy {
+    /* Połączona cząsteczka może wyjść poza przedziały; te ograniczenia dotyczą tylko
+     * cząsteczek wstawianych przez użytkownika. Masa, ładunek i pęd są zachowane. */
+    Allow,
+    /* Ładunek i masa są przycinane do dozwolonych przedziałów, a nadmiar po prostu znika.
+     * Zachowana jest prędkość środka masy, więc razem z nadmiarem masy ginie też część pędu. */
+    Clamp,
+    /* Takie cząsteczki się nie łączą, tylko odbijają jak przy `CollisionMode::Bounce`. */
+    Bounce,
+}
+
+impl MergePolicy {
+    pub const ALL: [MergePolicy; 3] = [MergePolicy::Allow, MergePolicy::Clamp, MergePolicy::Bounce];
+
+    pub fn name(&self) -> &'static str {
+        return match self {
+            MergePolicy::Allow => "Bez ograniczeń",
+            MergePolicy::Clamp => "Przycinanie",
+            MergePolicy::Bounce => "Odbicie",
+        };
+    }
+}
+
+/* Prostokątny obszar o innym polu magnetycznym niż reszta pudełka. */
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct MagneticRegion {
+    pub min: Vect,
+    pub max: Vect,
+    pub bz: Float,
+}
+
+/* Pole magnetyczne prostopadłe do płaszczyzny symulacji (ma tylko składową z).
+ *
+ * W punkcie należącym do któregoś z `regions` pole ma wartość z pierwszego takiego
+ * obszaru, a wszędzie indziej `uniform`. */
+#[derive(Clone, PartialEq, Debug)]
+pub struct MagneticField {
+    pub uniform: Float,
+    pub regions: Vec<MagneticRegion>,
+}
+
+impl MagneticField {
+    pub const NONE: MagneticField = MagneticField {
+        uniform: 0.0,
+        regions: Vec::new(),
+    };
+
+    /* Składowa z pola w punkcie `position`. */
+    pub fn at(&self, position: Vect) -> Float {
+        return self.regions
+            .iter()
+            .find(|r| r.min.x <= position.x && position.x <= r.max.x && r.min.y <= position.y && position.y <= r.max.y)
+            .map_or(self.uniform, |r| r.bz);
+    }
+
+    pub fn is_zero(&self) -> bool {
+        return self.uniform == 0.0 && self.regions.iter().all(|r| r.bz == 0.0);
+    }
+}
+
+/* Przebieg zewnętrznego pola elektrycznego w czasie. */
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Waveform {
+    Constant,
+    /* Pole zmienne E * sin(2π f t). */
+    Sine,
+    /* Prostokątne impulsy: pole E przez ułamek `duty_cycle` każdego okresu, a poza tym zero. */
+    Pulses,
+}
+
+impl Waveform {
+    pub const ALL: [Waveform; 3] = [Waveform::Constant, Waveform::Sine, Waveform::Pulses];
+
+    pub fn name(&self) -> &'static str {
+        return match self {
+            Waveform::Constant => "Stałe",
+            Waveform::Sine => "Sinusoidalne",
+            Waveform::Pulses => "Impulsy",
+        };
+    }
+}
+
+/* Zewnętrzne pole elektryczne, jednorodne w całym pudełku, ale zależne od czasu. */
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct ElectricField {
+    pub waveform: Waveform,
+    /* Wartość (i kierunek) pola w maksimum. */
+    pub amplitude: Vect,
+    pub frequency: Float,
+    /* Jaką część okresu trwa impuls przy `Waveform::Pulses`. */
+    pub duty_cycle: Float,
+}
+
+impl ElectricField {
+    pub const NONE: ElectricField = ElectricField {
+        waveform: Waveform::Constant,
+        amplitude: Vect::new(0.0, 0.0),
+        frequency: 1.0,
+        duty_cycle: 0.5,
+    };
+
+    /* Pole w chwili `time`. */
+    pub fn at(&self, time: Float) -> Vect {
+        return match self.waveform {
+            Waveform::Constant => self.amplitude,
+            Waveform::Sine => self.amplitude * (2.0 * consts::PI * self.frequency * time).sin(),
+            Waveform::Pulses => {
+                if (self.frequency * time).rem_euclid(1.0) < self.duty_cycle { self.amplitude } else { Vect::zeros() }
+            }
+        };
+    }
+
+    pub fn is_zero(&self) -> bool {
+        return self.amplitude == Vect::zeros();
+    }
+}
+
+/* Zależność siły oporu od prędkości względem płynu. */
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum DragModel {
+    None,
+    /* Opór Stokesa, proporcjonalny do prędkości (małe cząsteczki, gęsty płyn). */
+    Linear,
+    /* Opór proporcjonalny do kwadratu prędkości (duże prędkości, np. opór powietrza). */
+    Quadratic,
+    /* Suma obu. */
+    Combined,
+}
+
+impl DragModel {
+    pub const ALL: [DragModel; 4] = [DragModel::None, DragModel::Linear, DragModel::Quadratic, DragModel::Combined];
+
+    pub fn name(&self) -> &'static str {
+        return match self {
+            DragModel::None => "Brak",
+            DragModel::Linear => "Liniowy",
+            DragModel::Quadratic => "Kwadratowy",
+            DragModel::Combined => "Oba",
+        };
+    }
+
+    pub fn linear(&self) -> bool {
+        return matches!(self, DragModel::Linear | DragModel::Combined);
+    }
+
+    pub fn quadratic(&self) -> bool {
+        return matches!(self, DragModel::Quadratic | DragModel::Combined);
+    }
+}
+
+/* Rodzaj grawitacji w symulacji. */
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum GravityMode {
+    /* Jednorodne pole skierowane w dół, jak na powierzchni Ziemi. */
+    Uniform,
+    /* Cząsteczki przyciągają się nawzajem, analogicznie do oddziaływania elektrostatycznego. */
+    Mutual,
+    /* Jedno i drugie naraz. */
+    Both,
+}
+
+impl GravityMode {
+    pub const ALL: [GravityMode; 3] = [GravityMode::Uniform, GravityMode::Mutual, GravityMode::Both];
+
+    pub fn name(&self) -> &'static str {
+        return match self {
+            GravityMode::Uniform => "Jednorodna",
+            GravityMode::Mutual => "Wzajemna",
+            GravityMode::Both => "Obie",
+        };
+    }
+
+    pub fn uniform(&self) -> bool {
+        return matches!(self, GravityMode::Uniform | GravityMode::Both);
+    }
+
+    pub fn mutual(&self) -> bool {
+        return matches!(self, GravityMode::Mutual | GravityMode::Both);
+    }
+}
+
+/* Dodatkowe oddziaływanie krótkozasięgowe między każdą parą cząsteczek. */
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum PairPotential {
+    None,
+    /* Potencjał Lennarda-Jonesa 4ε((σ/r)^12 - (σ/r)^6), obcięty w 2.5σ. */
+    LennardJones,
+    /* Weeks-Chandler-Andersen: tylko odpychająca część Lennarda-Jonesa (obcięcie w minimum
+     * potencjału, 2^(1/6)σ), czyli po prostu "miękkie twarde kule". */
+    Wca,
+}
+
+impl PairPotential {
+    pub const ALL: [PairPotential; 3] = [PairPotential::None, PairPotential::LennardJones, PairPotential::Wca];
+
+    pub fn name(&self) -> &'static str {
+        return match self {
+            PairPotential::None => "Brak",
+            PairPotential::LennardJones => "Lennard-Jones",
+            PairPotential::Wca => "WCA",
+        };
+    }
+
+    /* Odległość (w jednostkach σ), od której oddziaływanie jest pomijane. */
+    pub fn cutoff(&self) -> Float {
+        return match self {
+            PairPotential::None => 0.0,
+            PairPotential::LennardJones => 2.5,
+            PairPotential::Wca => 1.122_462,
+        };
+    }
+}
+
+/* Stałe fizyczne symulacji. Należą do aplikacji i są przekazywane do wszystkich funkcji
+ * liczących siły i ruch, więc można je zmieniać w trakcie działania programu.
+ *
+ * Wartości stałych możemy raczej dobrać na wyczucie, bo wszystkie wielkości fizyczne
+ * w tej symulacji są bez jednostek. */
+#[derive(Clone, PartialEq, Debug)]
+pub struct SimulationParameters {
+    /* Stała oddziaływania elektrostatycznego. */
+    pub electro_k: Float,
+    /* Tak jak wcześniej, wszystkie stałe można zastąpić jedną, więc
+     * grawitacja będzie po prostu proporcjonalna do masy. */
+    pub gravity_k: Float,
+    pub gravity_mode: GravityMode,
+    /* Stała wzajemnego przyciągania grawitacyjnego cząsteczek. */
+    pub mutual_gravity_k: Float,
+    pub drag_model: DragModel,
+    /* Współczynnik oporu kwadratowego. */
+    pub drag_k: Float,
+    /* Współczynnik oporu liniowego. */
+    pub linear_drag_k: Float,
+    /* Prędkość płynu, względem którego liczymy opór (np. wiatr albo przepływ w kanale). */
+    pub fluid_velocity: Vect,
+    /* Dla bardzo małych odległości siła 1/r rośnie do nieskończoności i spada numeryczna
+     * precyzja operacji na floatach (bez tego cząsteczki odlatują na koniec świata
+     * w niektórych symulacjach), więc oddziaływanie trzeba jakoś złagodzić. */
+    pub softening: Softening,
+    /* Kwadrat odległości, poniżej którego przy `Softening::HardCutoff` cząsteczki
+     * nie oddziałują elektrostatycznie. */
+    pub eps: Float,
+    /* Długość zmiękczenia h dla `Softening::Plummer` i `Softening::Spline`. */
+    pub softening_length: Float,
+    /* Oddziaływanie krótkozasięgowe dodawane do sił elektrostatycznych, żeby cząsteczki
+     * o przeciwnych ładunkach nie zapadały się w siebie. */
+    pub pair_potential: PairPotential,
+    /* Parametry σ (odległość) i ε (głębokość studni) dla `pair_potential`. */
+    pub lj_sigma: Float,
+    pub lj_epsilon: Float,
+    pub collision_mode: CollisionMode,
+    /* Współczynnik restytucji przy zderzeniach: 1 to zderzenia sprężyste,
+     * 0 to doskonale niesprężyste (cząsteczki zostają przy sobie). */
+    pub restitution: Float,
+    pub merge_policy: MergePolicy,
+    pub magnetic_field: MagneticField,
+    pub electric_field: ElectricField,
+    pub limits: Limits,
+    /* Przeszkody wewnątrz pudełka. */
+    pub obstacles: Vec<Obstacle>,
+}
+
+impl SimulationParameters {
+    pub const DEFAULT: SimulationParameters = SimulationParameters {
+        electro_k: 1.5,
+        gravity_k: 8.0,
+        gravity_mode: GravityMode::Uniform,
+        mutual_gravity_k: 1.0,
+        drag_model: DragModel::Quadratic,
+        drag_k: 0.1,
+        linear_drag_k: 0.5,
+        fluid_velocity: Vect::new(0.0, 0.0),
+        softening: Softening::Plummer,
+        eps: 0.0001,
+        softening_length: 0.01,
+        pair_potential: PairPotential::None,
+        lj_sigma: 0.03,
+        lj_epsilon: 1.0,
+        collision_mode: CollisionMode::None,
+        restitution: 1.0,
+        merge_policy: MergePolicy::Allow,
+        magnetic_field: MagneticField::NONE,
+        electric_field: ElectricField::NONE,
+        limits: Limits {
+            min: Vect::new(0.0, 0.0),
+            max: Vect::new(1.0, 1.0),
+            walls: [Wall::REFLECT; 4],
+        },
+        obstacles: Vec::new(),
+    };
+
+    /* Gotowe zestawy parametrów do wybrania w UI. */
+    pub fn presets() -> Vec<Preset> {
+        return vec![
+            /* Domyślne stałe, ale bez ruszania rozmiaru obszaru i przeszkód. */
+            Preset::new("Domyślne", |params| {
+                let limits = Limits { walls: Self::DEFAULT.limits.walls, ..params.limits };
+                let obstacles = std::mem::take(&mut params.obstacles);
+                *params = SimulationParameters { limits, obstacles, ..Self::DEFAULT };
+            }),
+            Preset::new("Tylko Coulomb", |params| {
+                params.electro_k = Self::DEFAULT.electro_k;
+                params.gravity_mode = GravityMode::Uniform;
+                params.gravity_k = 0.0;
+                params.drag_model = DragModel::None;
+            }),
+            Preset::new("Tylko grawitacja", |params| {
+                params.electro_k = 0.0;
+                params.gravity_mode = GravityMode::Uniform;
+                params.gravity_k = Self::DEFAULT.gravity_k;
+            }),
+            Preset::new("Bez oporu", |params| params.drag_model = DragModel::None),
+            Preset::new("Silny opór", |params| {
+                params.drag_model = DragModel::Quadratic;
+                params.drag_k = 1.0;
+            }),
+            Preset::new("Gaz twardych dysków", |params| {
+                params.electro_k = 0.0;
+                params.gravity_mode = GravityMode::Uniform;
+                params.gravity_k = 0.0;
+                params.drag_model = DragModel::None;
+                params.collision_mode = CollisionMode::Bounce;
+            }),
+            /* Bez ścian, czyli kawałek nieskończonego gazu. */
+            Preset::new("Gaz okresowy", |params| {
+                params.electro_k = 0.0;
+                params.gravity_mode = GravityMode::Uniform;
+                params.gravity_k = 0.0;
+                params.drag_model = DragModel::None;
+                params.collision_mode = CollisionMode::Bounce;
+                params.limits.walls = [Wall::WRAP; 4];
+            }),
+            /* Wydłużony obszar, z którego cząsteczki wylatują jednym końcem i wracają drugim. */
+            Preset::new("Długi kanał", |params| {
+                params.electro_k = 0.0;
+                params.gravity_mode = GravityMode::Uniform;
+                params.gravity_k = 0.0;
+                params.drag_model = DragModel::None;
+                params.collision_mode = CollisionMode::Bounce;
+                params.limits.set_size(4.0, 1.0);
+                params.limits.walls = [Wall::WRAP, Wall::WRAP, Wall::REFLECT, Wall::REFLECT];
+            }),
+            /* Cząsteczki porywane przez płynący w prawo płyn, jak pył na wietrze. */
+            Preset::new("Przepływ", |params| {
+                params.electro_k = 0.0;
+                params.drag_model = DragModel::Combined;
+                params.fluid_velocity = Vect::new(1.0, 0.0);
+                params.limits.walls = [Wall::WRAP, Wall::WRAP, Wall::REFLECT, Wall::REFLECT];
+            }),
+            /* Gorąca lewa i zimna prawa ściana, więc przez gaz płynie ciepło. */
+            Preset::new("Przewodzenie ciepła", |params| {
+                params.electro_k = 0.0;
+                params.gravity_mode = GravityMode::Uniform;
+                params.gravity_k = 0.0;
+                params.drag_model = DragModel::None;
+                params.collision_mode = CollisionMode::Bounce;
+                params.limits.walls = [
+                    Wall { behaviour: WallBehaviour::Thermal, temperature: 4.0, ..Wall::REFLECT },
+                    Wall { behaviour: WallBehaviour::Thermal, temperature: 0.25, ..Wall::REFLECT },
+                    Wall::REFLECT,
+                    Wall::REFLECT,
+                ];
+            }),
+            /* Przegroda z małym otworem: cząsteczki wstawione po jednej stronie powoli
+             * dyfundują na drugą. */
+            Preset::new("Dwie komory", |params| {
+                let limits = params.limits;
+                params.electro_k = 0.0;
+                params.gravity_mode = GravityMode::Uniform;
+                params.gravity_k = 0.0;
+                params.drag_model = DragModel::None;
+                params.collision_mode = CollisionMode::Bounce;
+                params.obstacles = vec![
+                    Obstacle::segment(limits.point(0.5, 0.0), limits.point(0.5, 0.45)),
+                    Obstacle::segment(limits.point(0.5, 0.55), limits.point(0.5, 1.0)),
+                ];
+            }),
+            Preset::new("Dwie szczeliny", |params| {
+                let limits = params.limits;
+                params.electro_k = 0.0;
+                params.gravity_mode = GravityMode::Uniform;
+                params.gravity_k = 0.0;
+                params.drag_model = DragModel::None;
+                params.obstacles = vec![
+                    Obstacle::segment(limits.point(0.5, 0.0), limits.point(0.5, 0.35)),
+                    Obstacle::segment(limits.point(0.5, 0.42), limits.point(0.5, 0.58)),
+                    Obstacle::segment(limits.point(0.5, 0.65), limits.point(0.5, 1.0)),
+                ];
+            }),
+            Preset::new("Lejek", |params| {
+                let limits = params.limits;
+                params.electro_k = 0.0;
+                params.collision_mode = CollisionMode::Bounce;
+                params.obstacles = vec![
+                    Obstacle::segment(limits.point(0.05, 0.8), limits.point(0.45, 0.4)),
+                    Obstacle::segment(limits.point(0.95, 0.8), limits.point(0.55, 0.4)),
+                ];
+            }),
+            /* Zamknięta klatka: ładunki w środku i na zewnątrz dalej się widzą (przeszkody
+             * nie są przewodnikami), ale nie mogą się przez nią przedostać. */
+            Preset::new("Klatka", |params| {
+                params.gravity_mode = GravityMode::Uniform;
+                params.gravity_k = 0.0;
+                params.obstacles = vec![Obstacle::rectangle(params.limits.point(0.3, 0.3), params.limits.point(0.7, 0.7))];
+            }),
+            /* Przyciąganie grawitacyjne i odpychanie ładunków tego samego znaku mogą się równoważyć. */
+            Preset::new("Naładowany pył", |params| {
+                params.electro_k = 0.5;
+                params.gravity_mode = GravityMode::Mutual;
+                params.mutual_gravity_k = 2.0;
+                params.drag_model = DragModel::None;
+            }),
+            /* Przeciwne ładunki się przyciągają, ale odpychanie WCA nie pozwala im się zapaść,
+             * a opór powoli wychładza układ, więc tworzy się sieć krystaliczna. */
+            Preset::new("Kryształ jonowy", |params| {
+                params.electro_k = Self::DEFAULT.electro_k;
+                params.gravity_mode = GravityMode::Uniform;
+                params.gravity_k = 0.0;
+                params.drag_model = DragModel::Quadratic;
+                params.drag_k = 0.5;
+                params.pair_potential = PairPotential::Wca;
+                params.lj_sigma = 0.04;
+            }),
+            Preset::new("Cyklotron", |params| {
+                params.electro_k = 0.0;
+                params.gravity_mode = GravityMode::Uniform;
+                params.gravity_k = 0.0;
+                params.drag_model = DragModel::None;
+                params.magnetic_field = MagneticField { uniform: 10.0, regions: vec![] };
+            }),
+            /* Grawitacja działa tu jak pole elektryczne w dryfie E x B: cząsteczki
+             * dryfują w poziomie zamiast spadać, w różne strony zależnie od znaku ładunku. */
+            Preset::new("Dryf w polu grawitacji", |params| {
+                params.electro_k = 0.0;
+                params.gravity_mode = GravityMode::Uniform;
+                params.gravity_k = Self::DEFAULT.gravity_k;
+                params.drag_model = DragModel::None;
+                params.magnetic_field = MagneticField { uniform: 20.0, regions: vec![] };
+            }),
+            /* Przy stałym polu i oporze cząsteczki szybko osiągają stałą prędkość dryfu,
+             * tym większą, im większy stosunek ładunku do oporu. */
+            Preset::new("Dryf jonów", |params| {
+                params.electro_k = 0.0;
+                params.gravity_mode = GravityMode::Uniform;
+                params.gravity_k = 0.0;
+                params.drag_model = DragModel::Linear;
+                params.linear_drag_k = 1.0;
+                params.electric_field = ElectricField { amplitude: Vect::new(5.0, 0.0), ..ElectricField::NONE };
+                params.limits.walls = [Wall::WRAP, Wall::WRAP, Wall::REFLECT, Wall::REFLECT];
+            }),
+            /* Pole zmienne o częstotliwości cyklotronowej qB / (2π m) dla domyślnych cząsteczek
+             * (q = 0.5, m = 0.5), więc te cząsteczki krążą po coraz większych okręgach. */
+            Preset::new("Rezonans cyklotronowy", |params| {
+                params.electro_k = 0.0;
+                params.gravity_mode = GravityMode::Uniform;
+                params.gravity_k = 0.0;
+                params.drag_model = DragModel::None;
+                params.magnetic_field = MagneticField { uniform: 10.0, regions: vec![] };
+                params.electric_field = ElectricField {
+                    waveform: Waveform::Sine,
+                    amplitude: Vect::new(1.0, 0.0),
+                    frequency: 10.0 / (2.0 * consts::PI),
+                    ..ElectricField::NONE
+                };
+            }),
+            Preset::new("Butelka magnetyczna", |params| {
+                let limits = params.limits;
+                params.electro_k = 0.0;
+                params.gravity_mode = GravityMode::Uniform;
+                params.gravity_k = 0.0;
+                params.drag_model = DragModel::None;
+                params.magnetic_field = MagneticField {
+                    uniform: 2.0,
+                    regions: vec![
+                        MagneticRegion { min: limits.point(0.0, 0.0), max: limits.point(0.15, 1.0), bz: 30.0 },
+                        MagneticRegion { min: limits.point(0.85, 0.0), max: limits.point(1.0, 1.0), bz: 30.0 },
+                    ],
+                };
+            }),
+        ];
+    }
+
+    /* `force_kernel_2d` albo `force_kernel_3d`, zależnie od liczby wymiarów `D`. */
+    pub fn force_kernel<const D: usize>(&self, r_len_sq: Float) -> Float {
+        return match D {
+            2 => self.force_kernel_2d(r_len_sq),
+            3 => self.force_kernel_3d(r_len_sq),
+            _ => unreachable!("symulacja jest tylko w 2D i 3D"),
+        };
+    }
+
+    /* `potential_kernel_2d` albo `potential_kernel_3d`, zależnie od liczby wymiarów `D`. */
+    pub fn potential_kernel<const D: usize>(&self, r_len_sq: Float) -> Float {
+        return match D {
+            2 => self.potential_kernel_2d(r_len_sq),
+            3 => self.potential_kernel_3d(r_len_sq),
+            _ => unreachable!("symulacja jest tylko w 2D i 3D"),
+        };
+    }
+
+    /* Współczynnik f taki, że siła elektrostatyczna to k * q1 * q2 * f * r, gdzie r to wektor
+     * między ładunkami, a `r_len_sq` to jego kwadrat długości. Bez zmiękczenia f = 1 / r^2
+     * (w 2D siła maleje jak 1/r). */
+    pub fn force_kernel_2d(&self, r_len_sq: Float) -> Float {
+        /* Wszystkie wartości `r_len_sq`, które nie są skończone (czyli NaN albo nieskończoność)
+         * musimy zignorować. Przy r = 0 wektor r jest zerowy, więc siła i tak jest zerowa,
+         * a unikamy dzielenia przez zero. */
+        if !r_len_sq.is_finite() || r_len_sq == 0.0 {
+            return 0.0;
+        }
+
+        let h_sq = self.softening_length.powi(2);
+
+        return match self.softening {
+            Softening::HardCutoff => if r_len_sq < self.eps { 0.0 } else { 1.0 / r_len_sq },
+            Softening::Plummer => 1.0 / (r_len_sq + h_sq),
+            Softening::Spline => {
+                if r_len_sq >= h_sq {
+                    1.0 / r_len_sq
+                } else {
+                    /* Wartość siły to r/h^2 * (2 - r^2/h^2): przy r = h daje 1/r i taką samą
+                     * pochodną jak 1/r, a przy r = 0 spada płynnie do zera. */
+                    (2.0 - r_len_sq / h_sq) / h_sq
+                }
+            }
+        };
+    }
+
+    /* Potencjał ładunków (bez k * q1 * q2) zgodny z `force_kernel_2d`, czyli taki, że
+     * -grad potencjału daje siłę. Bez zmiękczenia to -ln(r). */
+    pub fn potential_kernel_2d(&self, r_len_sq: Float) -> Float {
+        if !r_len_sq.is_finite() {
+            return 0.0;
+        }
+
+        let h_sq = self.softening_length.powi(2);
+
+        return match self.softening {
+            /* W środku siły nie ma, więc potencjał jest stały. */
+            Softening::HardCutoff => -0.5 * r_len_sq.max(self.eps).ln(),
+            Softening::Plummer => -0.5 * (r_len_sq + h_sq).ln(),
+            Softening::Spline => {
+                if r_len_sq >= h_sq {
+                    -0.5 * r_len_sq.ln()
+                } else {
+                    let x = r_len_sq / h_sq;
+                    -0.5 * h_sq.ln() + 0.75 - x + x * x / 4.0
+                }
+            }
+        };
+    }
+
+    /* Odpowiednik `force_kernel_2d` w trybie 3D, gdzie siła maleje jak 1/r^2, więc bez
+     * zmiękczenia f = 1 / r^3. Zmiękczenie działa tak samo, tylko z wykładnikami dla 3D. */
+    pub fn force_kernel_3d(&self, r_len_sq: Float) -> Float {
+        if !r_len_sq.is_finite() || r_len_sq == 0.0 {
+            return 0.0;
+        }
+
+        let h_sq = self.softening_length.powi(2);
+
+        return match self.softening {
+            Softening::HardCutoff => if r_len_sq < self.eps { 0.0 } else { r_len_sq.powf(-1.5) },
+            Softening::Plummer => (r_len_sq + h_sq).powf(-1.5),
+            Softening::Spline => {
+                if r_len_sq >= h_sq {
+                    r_len_sq.powf(-1.5)
+                } else {
+                    /* Wartość siły to r/h^3 * (5/2 - 3/2 r^2/h^2): przy r = h daje 1/r^2
+                     * i taką samą pochodną jak 1/r^2. */
+                    (2.5 - 1.5 * r_len_sq / h_sq) / (h_sq * h_sq.sqrt())
+                }
+            }
+        };
+    }
+
+    /* Potencjał zgodny z `force_kernel_3d`. Bez zmiękczenia to 1/r. */
+    pub fn potential_kernel_3d(&self, r_len_sq: Float) -> Float {
+        if !r_len_sq.is_finite() {
+            return 0.0;
+        }
+
+        let h_sq = self.softening_length.powi(2);
+
+        return match self.softening {
+            Softening::HardCutoff => 1.0 / r_len_sq.max(self.eps).sqrt(),
+            Softening::Plummer => 1.0 / (r_len_sq + h_sq).sqrt(),
+            Softening::Spline => {
+                if r_len_sq >= h_sq {
+                    1.0 / r_len_sq.sqrt()
+                } else {
+                    let x = r_len_sq / h_sq;
+                    (1.875 - 1.25 * x + 0.375 * x * x) / h_sq.sqrt()
+                }
+            }
+        };
+    }
+}
+
+/* Gotowy zestaw parametrów. Ustawia tylko te pola, które opisuje, więc np. "Bez oporu"
+ * nie rusza rozmiaru obszaru ani przeszkód narysowanych przez użytkownika. Położenia
+ * przeszkód i obszarów pola są podane w ułamkach obszaru, więc pasują do każdego rozmiaru. */
+pub struct Preset {
+    pub name: &'static str,
+    apply: fn(&mut SimulationParameters),
+}
+
+impl Preset {
+    fn new(name: &'static str, apply: fn(&mut SimulationParameters)) -> Self {
+        return Self { name, apply };
+    }
+
+    pub fn apply(&self, params: &mut SimulationParameters) {
+        (self.apply)(params);
+    }
+}
+
+impl Default for SimulationParameters {
+    fn default() -> Self {
+        return Self::DEFAULT;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /* Sprawdza, czy `force` to -dU/dr / r dla potencjału `potential`, licząc pochodną
+     * różnicą centralną. Odległości są po obu stronach promienia zmiękczenia. */
+    fn assert_force_is_potential_gradient(
+        force: impl Fn(&SimulationParameters, Float) -> Float,
+        potential: impl Fn(&SimulationParameters, Float) -> Float,
+    ) {
+        for softening in Softening::ALL {
+            let params = SimulationParameters { softening, ..SimulationParameters::DEFAULT };
+
+            for r in [0.002, 0.005, 0.009, 0.011, 0.03, 0.2] {
+                let dr = 1e-3 * r;
+                let derivative = (potential(&params, (r + dr) * (r + dr)) - potential(&params, (r - dr) * (r - dr))) / (2.0 * dr);
+                let expected = -derivative / r;
+                let actual = force(&params, r * r);
+
+                assert!(
+                    (actual - expected).abs() <= 1e-2 * expected.abs().max(1.0),
+                    "{}, r = {}: siła {}, z potencjału {}", softening.name(), r, actual, expected,
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn kernels_2d_match_potential() {
+        assert_force_is_potential_gradient(SimulationParameters::force_kernel_2d, SimulationParameters::potential_kernel_2d);
+    }
+
+    #[test]
+    fn kernels_3d_match_potential() {
+        assert_force_is_potential_gradient(SimulationParameters::force_kernel_3d, SimulationParameters::potential_kernel_3d);
+    }
+}
@@ -1,158 +1,271 @@
 extern crate nalgebra as na;
-use core::ops::Deref;
+
+use rand::Rng;
+
+use crate::parameters::{Domain, WallBehaviour};
+
+/* Typ liczb zmiennoprzecinkowych w fizyce symulacji. Domyślnie f32, a z cechą `f64`
+ * (`cargo run --features f64`) podwójna precyzja, przydatna przy długich przebiegach
+ * i sprawdzaniu zachowania energii. UI i wykresy i tak liczą w swoich typach. */
+#[cfg(not(feature = "f64"))]
+pub type Float = f32;
+#[cfg(feature = "f64")]
+pub type Float = f64;
+
+/* Stałe matematyczne (np. `consts::PI`) w typie `Float`. */
+#[cfg(not(feature = "f64"))]
+pub use std::f32::consts;
+#[cfg(feature = "f64")]
+pub use std::f64::consts;
+
+/* Wektor w przestrzeni o `D` wymiarach (2 albo 3). */
+pub type VectN<const D: usize> = na::SVector<Float, D>;
 
 /* Zwykły wektor 2D, żeby nie pisać tego tyle razy. */
-pub type Vect = na::Vector2<f32>;
+pub type Vect = VectN<2>;
+
+/* Cząsteczka na płaszczyźnie. Tryb 3D ma `Particle3D`, czyli to samo w trzech wymiarach. */
+pub type Particle = Body<2>;
 
-pub struct Particle {
+/* Cząsteczka w przestrzeni o `D` wymiarach. Cała fizyka (siły, integratory, zderzenia,
+ * wiązania, termostaty) jest napisana dla dowolnego `D`, więc oba tryby liczą się tak samo. */
+#[derive(Clone)]
+pub struct Body<const D: usize> {
     /* Unikalne ID potrzebne do śledzenia cząsteczki. */
     pub id: u32,
-    pub position: Vect,
-    pub velocity: Vect,
+    pub position: VectN<D>,
+    pub velocity: VectN<D>,
     /* Aktualne przyspieszenie na potrzeby rysowania wykresów. */
-    pub acceleration: Vect,
+    pub acceleration: VectN<D>,
     /* Cząsteczki mają oddziaływać elektrostatycznie i grawitacyjnie. */
-    pub charge: f32,
-    pub mass: f32,
+    pub charge: Float,
+    pub mass: Float,
+    /* Promień cząsteczki, używany przy zderzeniach. */
+    pub radius: Float,
+    /* Nieruchoma cząsteczka (np. jądro albo ładunek na elektrodzie) dalej działa siłą
+     * na inne, ale sama się nie porusza: przy zderzeniach i wiązaniach zachowuje się
+     * jak cząsteczka o nieskończonej masie. */
+    pub fixed: bool,
 }
 
 impl Particle {
-    pub fn new(id: u32, pos_x: f32, pos_y: f32, charge: f32, mass: f32) -> Self {
+    pub fn new(id: u32, pos_x: Float, pos_y: Float, charge: Float, mass: Float, radius: Float) -> Self {
+        return Self::from_position(id, Vect::new(pos_x, pos_y), charge, mass, radius);
+    }
+}
+
+impl<const D: usize> Body<D> {
+    /* Największy promień wstawianej cząsteczki i rozrzut przy wstawianiu kilku naraz,
+     * dla obszaru o skali `Domain::extent` równej 1. */
+    pub const MAX_RADIUS: Float = 0.1;
+    pub const SPAWN_SPREAD: Float = 0.1;
+
+    pub fn from_position(id: u32, position: VectN<D>, charge: Float, mass: Float, radius: Float) -> Self {
     /* Aby ustalić skalę wszystkich wielkości w symulacji i dobrze ustawić stałe,
      * wszystkie te wartości muszą być z konkretnych przedziałów. */
-        assert!(Self::valid(pos_x, pos_y, charge, mass));
+        assert!(position.iter().all(|x| x.is_finite()) && Self::valid_properties(charge, mass, radius));
 
         return Self {
             id,
-            position: [pos_x, pos_y].into(),
-            velocity: [0.0, 0.0].into(),
-            acceleration: [0.0, 0.0].into(),
+            position,
+            velocity: VectN::zeros(),
+            acceleration: VectN::zeros(),
             charge,
             mass,
+            radius,
+            fixed: false,
         };
     }
 
-    pub fn valid(pos_x: f32, pos_y: f32, charge: f32, mass: f32) -> bool {
+    /* Czy taką cząsteczkę można wstawić do obszaru symulacji `domain`. */
+    pub fn valid(position: VectN<D>, charge: Float, mass: Float, radius: Float, domain: &impl Domain<D>) -> bool {
+        return domain.contains(position)
+            && Self::valid_properties(charge, mass, radius)
+            && radius <= Self::MAX_RADIUS * domain.extent();
+    }
+
+    /* Promień ma tu tylko być dodatni, bo górne ograniczenie zależy od wielkości obszaru. */
+    pub fn valid_properties(charge: Float, mass: Float, radius: Float) -> bool {
         return
-            0.0 <= pos_x && pos_x <= 1.0 &&
-            0.0 <= pos_y && pos_y <= 1.0 &&
-            -1.0 <= charge && charge <= 1.0 &&
-            0.0 < mass && mass <= 1.0;
+            (-1.0..=1.0).contains(&charge) &&
+            0.0 < mass && mass <= 1.0 &&
+            0.0 < radius;
     }
 
-    /* Wektor siły oddziaływania elektrostatycznego z cząsteczką `other`. */
-    pub fn electrostatic_force(&self, other: &Particle) -> Vect {
-        /* Wartości stałych możemy raczej dobrać na wyczucie,
-         * bo wszystkie wielkości fizyczne w tej symulacji są
-         * bez jednostek. */
-        const ELECTRO_K: f32 = 1.5;
-
-        let r = self.position - other.position;
-        let r_len_sq = r.magnitude_squared();
-
-        /* `r_len_sq` może być zero, gdy dwie cząsteczki się na siebie nałożą.
-         * Dla bardzo małych `r_len_sq` spada numeryczna precyzja operacji
-         * na floatach, dlatego dostatecznie małe wartości powinniśmy traktować jak 0
-         * (bez tego cząsteczki odlatują na koniec świata w niektórych symulacjach). */
-        const EPS: f32 = 0.0001;
-
-        /* Wszystkie wartości `r_len_sq`, które nie są skończone (czyli NaN albo nieskończoność)
-         * musimy zignorować. */
-        if !r_len_sq.is_finite() || r_len_sq < EPS {
-            return Vect::zeros();
-        } else {
-            return ELECTRO_K * self.charge * other.charge * r / r_len_sq;
-        }
+    /* Odwrotność masy, czyli zero dla nieruchomych cząsteczek. */
+    pub fn inverse_mass(&self) -> Float {
+        return if self.fixed { 0.0 } else { 1.0 / self.mass };
     }
 
-    /* Elektrostatyczna siła wypadkowa działająca na `self`, czyli, suma sił
-     * oddziaływań elektrostatycznych z każdą cząsteczką z `particles`. */
-    pub fn net_electrostatic_force(
-        &self,
-        particles: impl Iterator<Item = impl Deref<Target = Particle>>,
-    ) -> Vect {
-        return particles.fold(Vect::zeros(), |acc, p| acc + self.electrostatic_force(&*p));
+    /* Siła Lorentza q * v x B dla pola magnetycznego o składowej z równej `bz`.
+     * Ta siła zależy od prędkości, więc nie jest częścią `Interactions`, tylko dolicza ją
+     * integrator (a metoda Borisa obsługuje ją zupełnie osobno). */
+    pub fn lorentz_force(&self, bz: Float) -> VectN<D> {
+        return self.charge * bz * cross_z(self.velocity);
     }
 
-    /* Wektor siły grawitacyjnej. */
-    pub fn gravitational_force(&self) -> Vect {
-        /* Tak jak wcześniej, wszystkie stałe można zastąpić jedną, więc
-         * grawitacja będzie po prostu proporcjonalna do masy */
-        const GRAVITY_K: f32 = 8.0;
+    /* Obsługuje ściany obszaru, jeśli cząsteczka z niego wyleciała, zależnie od ich
+     * `WallBehaviour`. Zwraca indeks ściany (jak w `Domain::wall`), jeśli ta cząsteczkę
+     * pochłonęła i trzeba ją usunąć. Losowość jest potrzebna dla ścian termicznych.
+     * Popęd przekazany ścianie przy odbiciu jest dodawany do `wall_impulses` (do pomiaru ciśnienia).
+     * Samo całkowanie ruchu jest w `Integrator::step`, a siły w `force.rs`. */
+    pub fn apply_limits(&mut self, domain: &impl Domain<D>, rng: &mut impl Rng, wall_impulses: &mut [Float]) -> Option<usize> {
+        /* Nieruchomą cząsteczkę można zostawić poza obszarem, np. po jego zmniejszeniu. */
+        if self.fixed {
+            return None;
+        }
 
-        return Vect::from([0.0, -GRAVITY_K * self.mass]);
-    }
+        let (min, max) = domain.bounds();
+        let size = max - min;
 
-    /* Uaktualnia prędkość i pozycję `self` pod wpływem działania siły `force`
-     * przez czas `d_time` (to `d_time` to jest taka jakby różniczka czasu). */
-    pub fn apply_force(&mut self, force: Vect, d_time: f32) {
-        /* Współczynnik sił oporu ruchu. */
-        const DRAG_K: f32 = 0.1;
-
-        /* Dla dostatecznie małych prędkości, opór przestaje działać i cząsteczka dalej już
-         * nie spowalnia, tylko utrzymuje stałą szybkość. Nie wiem czemu tak jest, pewnie błędy
-         * precyzji jak zawsze; jak starczy czasu to coś się z tym zrobi. */
-        let drag_force = if self.velocity == Vect::zeros() {
-            Vect::zeros()
-        } else {
-            /* Opór powietrza jest proporcjonalny do v^2, o przeciwnym zwrocie. */
-            if !self.velocity.magnitude_squared().is_finite() {
-                Vect::zeros()
-            } else {
-                DRAG_K * self.velocity.magnitude_squared() * (-self.velocity.normalize())
-            }
-        };
+        for axis in 0..D {
+            for (side, edge) in [min[axis], max[axis]].into_iter().enumerate() {
+                /* Kierunek od ściany do środka obszaru. */
+                let inward = if side == 0 { 1.0 } else { -1.0 };
+                let outside = if side == 0 { self.position[axis] < edge } else { self.position[axis] > edge };
+
+                if !outside {
+                    continue;
+                }
 
-        /* Zakładamy, że przyspieszenie jest stałe w przedziale czasu `d_time`. */
-        let acceleration = (force + drag_force) / self.mass;
+                let index = 2 * axis + side;
+                let wall = domain.wall(index);
+                let normal_velocity = self.velocity[axis];
 
-        /* Zmiana prędkości to pole pod wykresem a(t). */
-        let d_velocity = acceleration * d_time;
+                match wall.behaviour {
+                    WallBehaviour::Reflect => {
+                        self.position[axis] = edge;
+                        self.velocity[axis] = inward * self.velocity[axis].abs() * wall.restitution;
+                    }
 
-        /* Zmiana położenia pole pod wykresem v(t); v rośnie liniowo. */
-        let d_position = d_time * self.velocity + d_time * d_velocity / 2.0;
+                    WallBehaviour::Absorb => return Some(index),
 
-        self.velocity += d_velocity;
-        self.position += d_position;
-        self.acceleration = acceleration;
+                    WallBehaviour::Wrap => {
+                        self.position[axis] = min[axis] + (self.position[axis] - min[axis]).rem_euclid(size[axis]);
+                    }
 
-        /* To są współrzędne pudełka ograniczającego ruch cząsteczek.
-         * To pewnie nie powinno być zahardcodowane, ale niech na razie
-         * tak zostanie. */
-        struct Limits {
-            min: Vect,
-            max: Vect,
+                    WallBehaviour::Thermal => {
+                        /* Cząsteczki odlatujące od ściany o temperaturze T mają składowe równoległe
+                         * z rozkładu normalnego o wariancji T/m, a prostopadłą z rozkładu Rayleigha
+                         * (szybsze cząsteczki częściej uderzają w ścianę, więc to nie jest po prostu
+                         * połowa rozkładu normalnego). */
+                        let sigma = (wall.temperature / self.mass).sqrt();
+                        let normal = sigma * (-2.0 * (1.0 - rng.gen::<Float>()).ln()).sqrt();
+
+                        self.position[axis] = edge;
+                        self.velocity = sigma * VectN::from_fn(|_, _| standard_normal(rng));
+                        self.velocity[axis] = inward * normal;
+                    }
+                }
+
+                wall_impulses[index] += self.mass * (self.velocity[axis] - normal_velocity).abs();
+            }
         }
-        const LIMITS: Limits = Limits {
-            min: Vect::new(0.0, 0.0),
-            max: Vect::new(1.0, 1.0),
-        };
 
-        if self.position.x < LIMITS.min.x {
-            self.position.x = LIMITS.min.x;
-            self.velocity.x *= -1.0;
+        return None;
+    }
+
+    /* Środek masy systemu cząsteczek.
+     *
+     * Na osiach okresowych zwykła średnia nie działa: grupa cząsteczek przechodząca przez brzeg
+     * ma część przy jednej ścianie, a część przy drugiej, i średnia wychodzi na środku pudełka.
+     * Dlatego tam zamieniamy współrzędną na punkt na okręgu, uśredniamy te punkty i zamieniamy
+     * kąt wyniku z powrotem na współrzędną. */
+    pub fn center_of_mass<'a>(particles: impl Iterator<Item = &'a Body<D>>, domain: &impl Domain<D>) -> Option<VectN<D>> {
+        let (min, max) = domain.bounds();
+        let size = max - min;
+        let mut total_mass = 0.0;
+        let mut position = VectN::zeros();
+        let mut circle = [Vect::zeros(); D];
+
+        for p in particles {
+            total_mass += p.mass;
+            position += p.position * p.mass;
+
+            for (axis, point) in circle.iter_mut().enumerate() {
+                let angle = 2.0 * consts::PI * (p.position[axis] - min[axis]) / size[axis];
+                *point += Vect::new(angle.cos(), angle.sin()) * p.mass;
+            }
         }
 
-        if self.position.x > LIMITS.max.x {
-            self.position.x = LIMITS.max.x;
-            self.velocity.x *= -1.0;
+        if total_mass == 0.0 {
+            return None;
         }
-        if self.position.y < LIMITS.min.y {
-            self.position.y = LIMITS.min.y;
-            self.velocity.y *= -1.0;
+
+        let mut center = position / total_mass;
+
+        for (axis, periodic) in domain.periodic_axes().into_iter().enumerate() {
+            /* Gdy punkty na okręgu się znoszą (np. równomiernie rozłożony gaz), środek
+             * jest nieokreślony i zostawiamy zwykłą średnią. */
+            if periodic && circle[axis].magnitude_squared() > 0.0 {
+                let angle = circle[axis].y.atan2(circle[axis].x).rem_euclid(2.0 * consts::PI);
+                center[axis] = min[axis] + angle / (2.0 * consts::PI) * size[axis];
+            }
         }
-        if self.position.y > LIMITS.max.y {
-            self.position.y = LIMITS.max.y;
-            self.velocity.y *= -1.0;
+
+        return Some(center);
+    }
+}
+
+/* Składowe x i y wektora. Pola zewnętrzne (elektryczne, magnetyczne, przepływ płynu) są
+ * określone na płaszczyźnie xy, a w 3D nie zależą od z. */
+pub fn plane<const D: usize>(v: VectN<D>) -> Vect {
+    return v.fixed_resize::<2, 1>(0.0);
+}
+
+/* Wektor z płaszczyzny xy w przestrzeni o `D` wymiarach (pozostałe składowe są zerowe). */
+pub fn from_plane<const D: usize>(v: Vect) -> VectN<D> {
+    return v.fixed_resize::<D, 1>(0.0);
+}
+
+/* Iloczyn wektorowy v x z, czyli `v` obrócony w płaszczyźnie xy o -90°. Składowa wzdłuż z
+ * (w 3D) znika, bo pole magnetyczne jest zawsze skierowane wzdłuż z. */
+pub fn cross_z<const D: usize>(v: VectN<D>) -> VectN<D> {
+    return from_plane(Vect::new(v[1], -v[0]));
+}
+
+/* Liczba losowa z rozkładu normalnego o średniej 0 i wariancji 1 (metoda Boxa-Mullera). */
+pub fn standard_normal(rng: &mut impl Rng) -> Float {
+    let u1 = 1.0 - rng.gen::<Float>();
+    let u2 = rng.gen::<Float>();
+
+    return (-2.0 * u1.ln()).sqrt() * (2.0 * consts::PI * u2).cos();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parameters::{Limits, Wall};
+
+    /* Pudełko 2 x 1 okresowe w poziomie i zamknięte w pionie. */
+    const LIMITS: Limits = Limits {
+        min: Vect::new(0.0, 0.0),
+        max: Vect::new(2.0, 1.0),
+        walls: [Wall::WRAP, Wall::WRAP, Wall::REFLECT, Wall::REFLECT],
+    };
+
+    #[test]
+    fn minimum_image_wraps_only_periodic_axes() {
+        for (r, expected) in [
+            (Vect::new(1.8, 0.9), Vect::new(-0.2, 0.9)),
+            (Vect::new(-1.3, -0.8), Vect::new(0.7, -0.8)),
+            (Vect::new(0.4, 0.2), Vect::new(0.4, 0.2)),
+        ] {
+            assert!((LIMITS.minimum_image(r) - expected).magnitude() < 1e-5, "{:?}", LIMITS.minimum_image(r));
         }
     }
 
-    /* Środek masy systemu cząsteczek. */
-    pub fn center_of_mass<'a>(particles: impl Iterator<Item = &'a Particle>) -> Option<Vect> {
-        let mut total_mass = 0.0;
-        let position = particles.fold(Vect::zeros(), |acc, p| { total_mass += p.mass; acc + p.position * p.mass });
+    #[test]
+    fn center_of_mass_across_periodic_edge() {
+        let particles = [Particle::new(0, 0.1, 0.3, 0.0, 0.5, 0.01), Particle::new(1, 1.9, 0.5, 0.0, 0.5, 0.01)];
+
+        /* Zwykła średnia dałaby środek pudełka (1, 0.4), a cząsteczki są przy brzegu x = 0. */
+        let center = Particle::center_of_mass(particles.iter(), &LIMITS).unwrap();
+        assert!(LIMITS.minimum_image(center - Vect::new(0.0, 0.4)).magnitude() < 1e-4, "{:?}", center);
 
-        return if total_mass != 0.0 { Some(position / total_mass) } 
-        else { None };
+        /* Bez okresowych ścian zostaje zwykła średnia. */
+        let closed = Limits { walls: [Wall::REFLECT; 4], ..LIMITS };
+        let center = Particle::center_of_mass(particles.iter(), &closed).unwrap();
+        assert!((center - Vect::new(1.0, 0.4)).magnitude() < 1e-5, "{:?}", center);
     }
 }
@@ -0,0 +1,48 @@
+use crate::parameters::{Domain, Wall};
+use crate::particle::{Body, Float, VectN};
+
+/* Wektor 3D, odpowiednik `Vect`. */
+pub type Vect3 = VectN<3>;
+
+/* Cząsteczka w trybie 3D. Fizyka jest wspólna z trybem 2D (patrz `Body`), tu jest tylko
+ * sześcian, w którym się porusza. */
+pub type Particle3D = Body<3>;
+
+/* Sześcian [min, min + size]^3 ograniczający ruch cząsteczek w trybie 3D.
+ * Wszystkie sześć ścian zachowuje się tak samo, zgodnie z `wall`. */
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Cube {
+    pub min: Float,
+    pub size: Float,
+    pub wall: Wall,
+}
+
+impl Cube {
+    pub const DEFAULT: Cube = Cube { min: 0.0, size: 1.0, wall: Wall::REFLECT };
+    /* Nazwy ścian w kolejności indeksów z `Domain::wall`. */
+    pub const WALL_NAMES: [&'static str; 6] = ["Lewa", "Prawa", "Dolna", "Górna", "Tylna", "Przednia"];
+
+    pub fn max(&self) -> Float {
+        return self.min + self.size;
+    }
+
+    pub fn center(&self) -> Vect3 {
+        return Vect3::repeat(self.min + self.size / 2.0);
+    }
+}
+
+impl Domain<3> for Cube {
+    fn bounds(&self) -> (Vect3, Vect3) {
+        return (Vect3::repeat(self.min), Vect3::repeat(self.max()));
+    }
+
+    fn wall(&self, _index: usize) -> Wall {
+        return self.wall;
+    }
+}
+
+impl Default for Cube {
+    fn default() -> Self {
+        return Self::DEFAULT;
+    }
+}
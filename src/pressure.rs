@@ -0,0 +1,137 @@
+use std::collections::VecDeque;
+
+use crate::energy::push;
+use crate::particle::Float;
+
+/* Pomiar ciśnienia na ścianach i temperatury kinetycznej, uśrednianych w oknie czasu `window`.
+ *
+ * Ciśnienie to popęd przekazany ścianom przy odbiciach (zbierany przez `apply_limits`)
+ * podzielony przez czas i pole ścian (w 2D ich długość). Liczą się tylko ściany, od których
+ * cząsteczki się odbijają, czyli odbijające i termiczne.
+ *
+ * Punkty krzywych P-T i P-N dopisujemy raz na pełne okno, żeby każdy był osobnym pomiarem,
+ * a nie średnią kroczącą nachodzącą na poprzednie. */
+pub struct PressureGauge {
+    /* Długość okna uśredniania w czasie symulacji. */
+    pub window: Float,
+    samples: VecDeque<Sample>,
+    /* Kiedy zaczęło się okno, z którego zbieramy następny punkt krzywych. */
+    window_start: Option<Float>,
+    /* Punkty (czas, średnia) dla ciśnienia i temperatury. */
+    pub pressure: VecDeque<[f64; 2]>,
+    pub temperature: VecDeque<[f64; 2]>,
+    /* Punkty (temperatura, ciśnienie) i (liczba cząsteczek, ciśnienie). */
+    pub pressure_temperature: VecDeque<[f64; 2]>,
+    pub pressure_count: VecDeque<[f64; 2]>,
+}
+
+/* Jedna klatka symulacji. */
+struct Sample {
+    time: Float,
+    d_time: Float,
+    /* Popęd przekazany każdej ścianie w tej klatce i ich pola (zero dla ścian bez odbić). */
+    impulses: Vec<Float>,
+    areas: Vec<Float>,
+    temperature: Float,
+    particle_count: usize,
+}
+
+/* Średnie z okna. */
+pub struct Averages {
+    /* Ciśnienie na każdej ścianie, `None` dla ścian bez odbić. */
+    pub walls: Vec<Option<Float>>,
+    /* Ciśnienie na wszystkich ścianach z odbiciami razem, `None`, jeśli takich nie ma. */
+    pub total: Option<Float>,
+    pub temperature: Float,
+    pub particle_count: Float,
+}
+
+impl PressureGauge {
+    /* Dla ilu chwil czasu chcemy trzymać wartości. */
+    pub const DATA_POINT_COUNT: usize = 1024;
+
+    pub fn new(window: Float) -> Self {
+        return Self {
+            window,
+            samples: VecDeque::new(),
+            window_start: None,
+            pressure: VecDeque::with_capacity(Self::DATA_POINT_COUNT),
+            temperature: VecDeque::with_capacity(Self::DATA_POINT_COUNT),
+            pressure_temperature: VecDeque::with_capacity(Self::DATA_POINT_COUNT),
+            pressure_count: VecDeque::with_capacity(Self::DATA_POINT_COUNT),
+        };
+    }
+
+    /* Czyści pomiary, zostawiając długość okna. */
+    pub fn clear(&mut self) {
+        *self = Self::new(self.window);
+    }
+
+    /* Zapisuje klatkę, która skończyła się w chwili `time` i trwała `d_time`.
+     * `impulses` i `areas` są w kolejności ścian z `Domain::wall` (4 w 2D, 6 w 3D). */
+    pub fn record(&mut self, time: Float, d_time: Float, impulses: &[Float], areas: &[Float], temperature: Float, particle_count: usize) {
+        if d_time <= 0.0 || !temperature.is_finite() || impulses.iter().any(|impulse| !impulse.is_finite()) {
+            return;
+        }
+
+        self.samples.push_back(Sample { time, d_time, impulses: impulses.to_vec(), areas: areas.to_vec(), temperature, particle_count });
+
+        while self.samples.front().is_some_and(|sample| sample.time - sample.d_time < time - self.window) {
+            self.samples.pop_front();
+        }
+
+        let Some(averages) = self.averages() else {
+            return;
+        };
+
+        let capacity = Self::DATA_POINT_COUNT;
+        push(&mut self.temperature, [time as f64, averages.temperature as f64], capacity);
+
+        let Some(pressure) = averages.total else {
+            self.window_start = None;
+            return;
+        };
+
+        push(&mut self.pressure, [time as f64, pressure as f64], capacity);
+
+        let window_start = *self.window_start.get_or_insert(time - d_time);
+        if time - window_start >= self.window {
+            push(&mut self.pressure_temperature, [averages.temperature as f64, pressure as f64], capacity);
+            push(&mut self.pressure_count, [averages.particle_count as f64, pressure as f64], capacity);
+            self.window_start = Some(time);
+        }
+    }
+
+    /* Średnie z klatek mieszczących się w oknie, `None`, jeśli jeszcze żadnej nie było. */
+    pub fn averages(&self) -> Option<Averages> {
+        let duration: Float = self.samples.iter().map(|sample| sample.d_time).sum();
+
+        if duration <= 0.0 {
+            return None;
+        }
+
+        let mut walls = vec![None; self.samples.front().map_or(0, |sample| sample.impulses.len())];
+        let (mut total_impulse, mut total_area) = (0.0, 0.0);
+
+        for (wall, pressure) in walls.iter_mut().enumerate() {
+            let impulse: Float = self.samples.iter().map(|sample| sample.impulses[wall]).sum();
+            /* Pole średnie w czasie, bo pudełko mogło się w oknie zmienić. */
+            let area: Float = self.samples.iter().map(|sample| sample.areas[wall] * sample.d_time).sum::<Float>() / duration;
+
+            if area > 0.0 {
+                *pressure = Some(impulse / (area * duration));
+                total_impulse += impulse;
+                total_area += area;
+            }
+        }
+
+        let weighted = |value: fn(&Sample) -> Float| self.samples.iter().map(|sample| value(sample) * sample.d_time).sum::<Float>() / duration;
+
+        return Some(Averages {
+            walls,
+            total: if total_area > 0.0 { Some(total_impulse / (total_area * duration)) } else { None },
+            temperature: weighted(|sample| sample.temperature),
+            particle_count: weighted(|sample| sample.particle_count as Float),
+        });
+    }
+}
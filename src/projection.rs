@@ -0,0 +1,109 @@
+use crate::particle::Float;
+use crate::particle3d::{Cube, Vect3};
+
+/* Rzut prostokątny trybu 3D na jedną z płaszczyzn układu współrzędnych. */
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Projection {
+    XY,
+    XZ,
+    YZ,
+}
+
+impl Projection {
+    pub const ALL: [Projection; 3] = [Projection::XY, Projection::XZ, Projection::YZ];
+
+    pub fn name(&self) -> &'static str {
+        return match self {
+            Projection::XY => "XY",
+            Projection::XZ => "XZ",
+            Projection::YZ => "YZ",
+        };
+    }
+
+    /* Indeksy osi rysowanych poziomo i pionowo. */
+    pub fn axes(&self) -> [usize; 2] {
+        return match self {
+            Projection::XY => [0, 1],
+            Projection::XZ => [0, 2],
+            Projection::YZ => [1, 2],
+        };
+    }
+
+    pub fn project(&self, point: Vect3) -> [f64; 2] {
+        let [horizontal, vertical] = self.axes();
+        return [point[horizontal] as f64, point[vertical] as f64];
+    }
+}
+
+/* Kamera perspektywiczna krążąca wokół środka sześcianu.
+ *
+ * `yaw` to obrót wokół pionowej osi y, a `pitch` pochylenie (dodatnie to patrzenie z góry).
+ * Przy `orbit` kamera sama się obraca z prędkością kątową `orbit_speed`. */
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Camera {
+    pub yaw: Float,
+    pub pitch: Float,
+    pub orbit: bool,
+    pub orbit_speed: Float,
+}
+
+impl Camera {
+    /* Odległość kamery od środka sześcianu, w długościach jego boku. */
+    const DISTANCE: Float = 2.5;
+    /* Pochylenie jest ograniczone, żeby kamera nie przeskoczyła nad biegunem. */
+    pub const PITCH_RANGE: std::ops::RangeInclusive<Float> = -1.5..=1.5;
+
+    pub fn new() -> Self {
+        return Self { yaw: 0.5, pitch: 0.4, orbit: true, orbit_speed: 0.3 };
+    }
+
+    /* Punkt `point` na płaszczyźnie obrazu, przeskalowany tak, że środek sześcianu ma
+     * prawdziwe rozmiary, oraz skala w tym punkcie (bliższe rzeczy są większe).
+     * `None` dla punktów za kamerą. */
+    pub fn project(&self, point: Vect3, cube: &Cube) -> Option<([f64; 2], Float)> {
+        let r = point - cube.center();
+        let (sin_yaw, cos_yaw) = self.yaw.sin_cos();
+        let (sin_pitch, cos_pitch) = self.pitch.sin_cos();
+
+        let x = r.x * cos_yaw + r.z * sin_yaw;
+        let z = -r.x * sin_yaw + r.z * cos_yaw;
+        let y = r.y * cos_pitch - z * sin_pitch;
+        let z = r.y * sin_pitch + z * cos_pitch;
+
+        let distance = Self::DISTANCE * cube.size;
+        let depth = distance - z;
+
+        if depth <= 0.0 {
+            return None;
+        }
+
+        let scale = distance / depth;
+        return Some(([(x * scale) as f64, (y * scale) as f64], scale));
+    }
+}
+
+impl Default for Camera {
+    fn default() -> Self {
+        return Self::new();
+    }
+}
+
+/* Dwanaście krawędzi sześcianu, do narysowania jego szkieletu. */
+pub fn cube_edges(cube: &Cube) -> Vec<(Vect3, Vect3)> {
+    let corner = |i: usize| Vect3::new(
+        if i & 1 == 0 { cube.min } else { cube.max() },
+        if i & 2 == 0 { cube.min } else { cube.max() },
+        if i & 4 == 0 { cube.min } else { cube.max() },
+    );
+
+    let mut edges = Vec::with_capacity(12);
+    for i in 0..8 {
+        for bit in [1, 2, 4] {
+            if i & bit == 0 {
+                edges.push((corner(i), corner(i | bit)));
+            }
+        }
+    }
+
+    return edges;
+}
@@ -0,0 +1,267 @@
+use crate::parameters::SimulationParameters;
+use crate::particle::{Body, Float, VectN};
+
+/* Sposób liczenia sił dalekozasięgowych (elektrostatycznych i wzajemnej grawitacji). */
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ForceBackend {
+    /* Suma po wszystkich parach, O(N^2). Dokładna, więc zostaje do sprawdzania drzewa. */
+    Direct,
+    /* Drzewo Barnesa-Huta (czwórkowe w 2D, ósemkowe w 3D), O(N log N). */
+    BarnesHut,
+}
+
+impl ForceBackend {
+    pub const ALL: [ForceBackend; 2] = [ForceBackend::Direct, ForceBackend::BarnesHut];
+
+    pub fn name(&self) -> &'static str {
+        return match self {
+            ForceBackend::Direct => "Bezpośrednio",
+            ForceBackend::BarnesHut => "Barnes-Hut",
+        };
+    }
+}
+
+/* Pojedyncze źródło pola (ładunek albo masa) zapisane w liściu drzewa. */
+struct Source<const D: usize> {
+    id: u32,
+    position: VectN<D>,
+    source: Float,
+}
+
+enum NodeKind<const D: usize> {
+    Leaf(Vec<Source<D>>),
+    /* 2^D dzieci, w kolejności jak w `Node::child_index`. */
+    Branch(Vec<Node<D>>),
+}
+
+struct Node<const D: usize> {
+    /* Środek i połowa boku kwadratu (w 3D sześcianu) zajmowanego przez węzeł. */
+    center: VectN<D>,
+    half_size: Float,
+    /* Suma źródeł wszystkich cząsteczek w węźle. */
+    source: Float,
+    /* Punkt, w którym umieszczamy całe źródło przy przybliżaniu węzła.
+     *
+     * Ładunki mogą mieć różne znaki, więc zwykły "środek ładunku" może wylecieć gdzieś
+     * daleko poza węzeł (albo w ogóle nie istnieć, gdy ładunki się znoszą). Dlatego ważymy
+     * położenia wartościami bezwzględnymi. Dla mas to jest zwykły środek masy. */
+    source_center: VectN<D>,
+    /* Moment dipolowy względem `source_center`. */
+    dipole: VectN<D>,
+    kind: NodeKind<D>,
+}
+
+/* Drzewo do przybliżonego liczenia pól metodą Barnesa-Huta: czwórkowe w 2D, ósemkowe w 3D.
+ *
+ * Każda cząsteczka jest źródłem o wartości z funkcji `source` podanej przy budowaniu
+ * (ładunek dla sił elektrostatycznych, masa dla grawitacji). Grupy cząsteczek dostatecznie
+ * daleko od punktu, w którym liczymy pole, są zastępowane jednym źródłem i dipolem.
+ * "Dostatecznie daleko" to bok węzła / odległość < `theta`. */
+pub struct QuadTree<const D: usize> {
+    root: Node<D>,
+}
+
+impl<const D: usize> QuadTree<D> {
+    /* W liściu może być więcej cząsteczek tylko wtedy, gdy są praktycznie w tym samym miejscu. */
+    const MAX_DEPTH: u32 = 16;
+
+    pub fn new(particles: &[Body<D>], source: impl Fn(&Body<D>) -> Float) -> Self {
+        /* Cząsteczki o zerowym źródle nie wytwarzają pola, więc nie ma po co ich wstawiać. */
+        let sources: Vec<Source<D>> = particles
+            .iter()
+            .filter(|p| source(p) != 0.0 && p.position.magnitude_squared().is_finite())
+            .map(|p| Source { id: p.id, position: p.position, source: source(p) })
+            .collect();
+
+        let (min, max) = sources.iter().fold(
+            (VectN::<D>::repeat(Float::INFINITY), VectN::<D>::repeat(Float::NEG_INFINITY)),
+            |(min, max), s| (min.inf(&s.position), max.sup(&s.position)),
+        );
+
+        let (center, half_size) = if sources.is_empty() {
+            (VectN::zeros(), 0.0)
+        } else {
+            ((min + max) / 2.0, (max - min).max() / 2.0)
+        };
+
+        return Self {
+            root: Node::new(sources, center, half_size, 0),
+        };
+    }
+
+    /* Przybliżona suma s * f(r) * r po wszystkich źródłach z drzewa (oprócz cząsteczki o ID
+     * `exclude_id`), gdzie s to wartość źródła, r wektor od źródła do `position`,
+     * a f to `params.force_kernel`. Żeby dostać siłę, trzeba to jeszcze pomnożyć przez stałą
+     * oddziaływania i ładunek (albo masę) cząsteczki w `position`. */
+    pub fn field(&self, position: VectN<D>, exclude_id: u32, theta: Float, params: &SimulationParameters) -> VectN<D> {
+        return self.root.field(position, exclude_id, theta, params);
+    }
+}
+
+impl<const D: usize> Node<D> {
+    fn new(sources: Vec<Source<D>>, center: VectN<D>, half_size: Float, depth: u32) -> Self {
+        let source = sources.iter().map(|s| s.source).sum::<Float>();
+        let abs_source = sources.iter().map(|s| s.source.abs()).sum::<Float>();
+        let source_center = if abs_source > 0.0 {
+            sources.iter().fold(VectN::zeros(), |acc, s| acc + s.position * s.source.abs()) / abs_source
+        } else {
+            center
+        };
+        let dipole = sources.iter().fold(VectN::zeros(), |acc, s| acc + (s.position - source_center) * s.source);
+
+        let kind = if sources.len() <= 1 || depth >= QuadTree::<D>::MAX_DEPTH {
+            NodeKind::Leaf(sources)
+        } else {
+            let mut children: Vec<Vec<Source<D>>> = (0..1 << D).map(|_| Vec::new()).collect();
+            for s in sources {
+                children[Self::child_index(center, s.position)].push(s);
+            }
+
+            let quarter = half_size / 2.0;
+            NodeKind::Branch(
+                children
+                    .into_iter()
+                    .enumerate()
+                    .map(|(index, sources)| {
+                        /* Bit `axis` indeksu mówi, po której stronie środka jest dziecko na tej osi. */
+                        let offset = VectN::from_fn(|axis, _| if index >> axis & 1 == 1 { quarter } else { -quarter });
+                        Node::new(sources, center + offset, quarter, depth + 1)
+                    })
+                    .collect(),
+            )
+        };
+
+        return Self { center, half_size, source, source_center, dipole, kind };
+    }
+
+    /* Indeks dziecka (w kolejności jak w `NodeKind::Branch`), do którego należy `position`. */
+    fn child_index(center: VectN<D>, position: VectN<D>) -> usize {
+        return (0..D).map(|axis| ((position[axis] >= center[axis]) as usize) << axis).sum();
+    }
+
+    fn contains(&self, position: VectN<D>) -> bool {
+        return (position - self.center).abs().max() <= self.half_size;
+    }
+
+    fn field(&self, position: VectN<D>, exclude_id: u32, theta: Float, params: &SimulationParameters) -> VectN<D> {
+        match &self.kind {
+            NodeKind::Leaf(sources) => {
+                return sources
+                    .iter()
+                    .filter(|s| s.id != exclude_id)
+                    .fold(VectN::zeros(), |acc, s| {
+                        let r = position - s.position;
+                        acc + s.source * params.force_kernel::<D>(r.magnitude_squared()) * r
+                    });
+            }
+
+            NodeKind::Branch(children) => {
+                let r = position - self.source_center;
+                let r_len_sq = r.magnitude_squared();
+
+                /* Jeśli punkt jest w środku węzła, to przybliżenie policzyłoby też oddziaływanie
+                 * cząsteczki samej ze sobą, więc wtedy zawsze schodzimy niżej. */
+                if !self.contains(position) && 4.0 * self.half_size.powi(2) < theta.powi(2) * r_len_sq {
+                    /* Źródło plus następny wyraz rozwinięcia, czyli dipol. Dipol jest potrzebny,
+                     * bo przy ładunkach obu znaków całkowity ładunek grupy często prawie się znosi.
+                     * Z rozwinięcia (r - d) / |r - d|^D dla małego d wychodzi
+                     * (D r (r.p) / r^2 - p) / r^D (w 2D: 2 r (r.p) / r^4 - p / r^2).
+                     * Węzeł jest daleko, więc zmiękczenie nie ma tu znaczenia. */
+                    return self.source * params.force_kernel::<D>(r_len_sq) * r
+                        + (D as Float * r * r.dot(&self.dipole) / r_len_sq - self.dipole) / r_len_sq.powf(D as Float / 2.0);
+                }
+
+                return children
+                    .iter()
+                    .fold(VectN::zeros(), |acc, c| acc + c.field(position, exclude_id, theta, params));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{Rng, SeedableRng};
+    use rand::rngs::StdRng;
+
+    use super::*;
+    use crate::force::Interactions;
+    use crate::parameters::Domain;
+    use crate::particle::{Particle, Vect};
+    use crate::particle3d::Cube;
+
+    /* Cząsteczki o ładunkach obu znaków, tak że całkowity ładunek prawie się znosi. */
+    fn mixed_charges<const D: usize>(count: u32, center: VectN<D>, spread: Float) -> Vec<Body<D>> {
+        let mut rng = StdRng::seed_from_u64(3);
+
+        return (0..count)
+            .map(|id| {
+                let position = center + spread * VectN::from_fn(|_, _| rng.gen_range(-1.0..1.0));
+                let charge = if id % 2 == 0 { 1.0 } else { -1.0 } * rng.gen_range(0.2..1.0);
+                Body::from_position(id, position, charge, 0.5, 0.01)
+            })
+            .collect();
+    }
+
+    fn direct_field(particles: &[Particle], position: Vect, params: &SimulationParameters) -> Vect {
+        return particles.iter().fold(Vect::zeros(), |acc, p| {
+            let r = position - p.position;
+            acc + p.charge * params.force_kernel_2d(r.magnitude_squared()) * r
+        });
+    }
+
+    fn relative_error(approximate: Vect, exact: Vect) -> Float {
+        return (approximate - exact).magnitude() / exact.magnitude();
+    }
+
+    /* Błąd względny sił z drzewa względem sumy po parach, dla wszystkich cząsteczek naraz. */
+    fn barnes_hut_error<const D: usize>(particles: &[Body<D>], domain: &impl Domain<D>, params: &SimulationParameters) -> Float {
+        let interactions = Interactions::builtin();
+
+        let direct = interactions.pair_forces(particles, params, domain, ForceBackend::Direct, 0.5);
+        let barnes_hut = interactions.pair_forces(particles, params, domain, ForceBackend::BarnesHut, 0.5);
+
+        let error: Float = direct.iter().zip(&barnes_hut).map(|(d, b)| (d - b).magnitude_squared()).sum();
+        let scale: Float = direct.iter().map(|d| d.magnitude_squared()).sum();
+
+        return (error / scale).sqrt();
+    }
+
+    #[test]
+    fn dipole_term_improves_far_field() {
+        let params = SimulationParameters::default();
+        let cluster = mixed_charges(64, Vect::zeros(), 0.1);
+        let tree = QuadTree::new(&cluster, |p| p.charge);
+        let position = Vect::new(1.3, 0.7);
+
+        let exact = direct_field(&cluster, position, &params);
+        let monopole = tree.root.source * params.force_kernel_2d((position - tree.root.source_center).magnitude_squared())
+            * (position - tree.root.source_center);
+        /* Przy dużym kącie otwarcia całe drzewo jest przybliżane korzeniem. */
+        let approximate = tree.field(position, u32::MAX, 1.0, &params);
+
+        /* Z błędnym znakiem dipol mniej więcej podwaja błąd samego źródła, zamiast go zmniejszać. */
+        assert!(relative_error(approximate, exact) < 0.5 * relative_error(monopole, exact));
+    }
+
+    #[test]
+    fn barnes_hut_matches_direct() {
+        let params = SimulationParameters::default();
+        let particles = mixed_charges(300, Vect::repeat(0.5), 0.5);
+        let error = barnes_hut_error(&particles, &params.limits, &params);
+
+        /* Siły od ładunków obu znaków częściowo się znoszą, więc błąd względny jest większy niż
+         * dla samych mas. Z odwróconym znakiem dipola wychodzi tu około 0.15. */
+        assert!(error < 3e-2, "błąd względny {}", error);
+    }
+
+    #[test]
+    fn octree_matches_direct() {
+        let params = SimulationParameters::default();
+        let cube = Cube::DEFAULT;
+        let particles = mixed_charges(300, cube.center(), 0.5);
+        let error = barnes_hut_error(&particles, &cube, &params);
+
+        assert!(error < 3e-2, "błąd względny {}", error);
+    }
+}
@@ -0,0 +1,132 @@
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use crate::particle::{standard_normal, Body, Float, VectN};
+
+/* Temperatura kinetyczna układu. Każda cząsteczka ma `D` stopni swobody, po 1/2 kT na każdy,
+ * a stała Boltzmanna jest równa 1, więc T to 2/D razy średnia energia kinetyczna 1/2 m v^2
+ * na cząsteczkę. W 2D to po prostu ta średnia, czyli to samo, co na histogramie energii.
+ *
+ * Nieruchome cząsteczki się nie liczą, tak samo jak te z nieskończoną prędkością. */
+pub fn kinetic_temperature<const D: usize>(particles: &[Body<D>]) -> Float {
+    let energies: Vec<Float> = particles
+        .iter()
+        .filter(|p| !p.fixed)
+        .map(|p| 0.5 * p.mass * p.velocity.magnitude_squared())
+        .filter(|e| e.is_finite())
+        .collect();
+
+    return if energies.is_empty() { 0.0 } else { 2.0 / D as Float * energies.iter().sum::<Float>() / energies.len() as Float };
+}
+
+/* Rodzaj termostatu. */
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ThermostatKind {
+    None,
+    /* Przeskalowanie wszystkich prędkości tak, żeby temperatura zbliżała się do docelowej
+     * wykładniczo z czasem `relaxation_time`. Nie daje poprawnego rozkładu prędkości,
+     * ale najszybciej się stabilizuje. */
+    Berendsen,
+    /* Tarcie o współczynniku 1 / `relaxation_time` plus losowe kopnięcia, dobrane tak,
+     * żeby prędkości dążyły do rozkładu Maxwella. */
+    Langevin,
+    /* Każda cząsteczka średnio raz na `relaxation_time` dostaje zupełnie nową prędkość
+     * z rozkładu Maxwella, jakby zderzyła się z cząsteczką kąpieli cieplnej. */
+    Andersen,
+}
+
+impl ThermostatKind {
+    pub const ALL: [ThermostatKind; 4] = [
+        ThermostatKind::None,
+        ThermostatKind::Berendsen,
+        ThermostatKind::Langevin,
+        ThermostatKind::Andersen,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        return match self {
+            ThermostatKind::None => "Brak",
+            ThermostatKind::Berendsen => "Berendsen",
+            ThermostatKind::Langevin => "Langevin",
+            ThermostatKind::Andersen => "Andersen",
+        };
+    }
+}
+
+/* Termostat utrzymujący temperaturę `kinetic_temperature` w pobliżu `temperature`.
+ *
+ * Ma własny generator liczb losowych z ziarnem `seed`, żeby przebiegi z termostatem
+ * Langevina i Andersena dało się powtórzyć. */
+pub struct Thermostat {
+    pub kind: ThermostatKind,
+    pub temperature: Float,
+    pub relaxation_time: Float,
+    pub seed: u64,
+    rng: StdRng,
+}
+
+impl Thermostat {
+    pub fn new(kind: ThermostatKind, temperature: Float, relaxation_time: Float, seed: u64) -> Self {
+        return Self {
+            kind,
+            temperature,
+            relaxation_time,
+            seed,
+            rng: StdRng::seed_from_u64(seed),
+        };
+    }
+
+    /* Zaczyna ciąg liczb losowych od początku (np. po zmianie ziarna albo przy nowej symulacji). */
+    pub fn reseed(&mut self) {
+        self.rng = StdRng::seed_from_u64(self.seed);
+    }
+
+    /* Działa termostatem przez czas `d_time`. Wywoływane po każdym kroku integratora. */
+    pub fn apply<const D: usize>(&mut self, particles: &mut [Body<D>], d_time: Float) {
+        let particles = particles
+            .iter_mut()
+            .filter(|p| !p.fixed && p.velocity.magnitude_squared().is_finite());
+
+        match self.kind {
+            ThermostatKind::None => {}
+
+            ThermostatKind::Berendsen => {
+                let particles: Vec<&mut Body<D>> = particles.collect();
+                let current = 2.0 / D as Float * particles.iter().map(|p| 0.5 * p.mass * p.velocity.magnitude_squared()).sum::<Float>()
+                    / particles.len().max(1) as Float;
+
+                /* Cząsteczek w spoczynku nie da się rozgrzać samym skalowaniem. */
+                if current > 0.0 {
+                    let scale = (1.0 + d_time / self.relaxation_time * (self.temperature / current - 1.0)).max(0.0).sqrt();
+
+                    for p in particles {
+                        p.velocity *= scale;
+                    }
+                }
+            }
+
+            ThermostatKind::Langevin => {
+                /* Dokładne rozwiązanie dv = -γ v dt + sqrt(2 γ T / m) dW na czas `d_time`,
+                 * więc działa poprawnie nawet dla kroków dłuższych niż 1 / γ. */
+                let decay = (-d_time / self.relaxation_time).exp();
+
+                for p in particles {
+                    let sigma = (self.temperature / p.mass * (1.0 - decay * decay)).sqrt();
+                    let kick = sigma * VectN::from_fn(|_, _| standard_normal(&mut self.rng));
+                    p.velocity = p.velocity * decay + kick;
+                }
+            }
+
+            ThermostatKind::Andersen => {
+                let probability = 1.0 - (-d_time / self.relaxation_time).exp();
+
+                for p in particles {
+                    if self.rng.gen::<Float>() < probability {
+                        let sigma = (self.temperature / p.mass).sqrt();
+                        p.velocity = sigma * VectN::from_fn(|_, _| standard_normal(&mut self.rng));
+                    }
+                }
+            }
+        }
+    }
+}